@@ -0,0 +1,183 @@
+//! `#[derive(Vertex)]` for `ash-opinionated-abstractions`. Use it through the `derive` feature of
+//! that crate rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, Member};
+
+/// Implement `Vertex` for a `#[repr(C)]` struct without padding.
+///
+/// Field types are mapped to a `VertexAttribute` through `VertexFormat`, which is implemented for
+/// `f32`, `i32`, `u32`, arrays of 2-4 of those, and `[[f32; 4]; 4]`. Other types, such as
+/// `[u8; 4]`, need an explicit format: `#[vertex(format = Unorm8x4)]`. Add `#[vertex(instance)]`
+/// to the struct for per-instance data.
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "Vertex can't be derived for generic structs",
+        ));
+    }
+
+    if !has_repr_c(input)? {
+        return Err(syn::Error::new_spanned(
+            name,
+            "Vertex requires #[repr(C)] so that the field order and offsets are fixed",
+        ));
+    }
+
+    let mut per_instance = false;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("vertex"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("instance") {
+                per_instance = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `instance`"))
+            }
+        })?;
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            Fields::Unnamed(fields) => &fields.unnamed,
+            Fields::Unit => {
+                return Err(syn::Error::new_spanned(name, "Vertex structs need fields"));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "Vertex can only be derived for structs",
+            ))
+        }
+    };
+
+    if fields.is_empty() {
+        return Err(syn::Error::new_spanned(name, "Vertex structs need fields"));
+    }
+
+    let krate = quote!(::ash_opinionated_abstractions);
+
+    let mut attributes = Vec::new();
+    let mut checks = Vec::new();
+    let mut previous_end = quote!(0usize);
+
+    for (index, field) in fields.iter().enumerate() {
+        let ty = &field.ty;
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(index.into()),
+        };
+        let field_name = match &field.ident {
+            Some(ident) => ident.to_string(),
+            None => index.to_string(),
+        };
+
+        let mut format: Option<Ident> = None;
+
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("vertex"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("format") {
+                    format = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `format = <VertexAttribute variant>`"))
+                }
+            })?;
+        }
+
+        let attribute = match format {
+            Some(format) => quote!(#krate::VertexAttribute::#format),
+            None => quote!(<#ty as #krate::VertexFormat>::ATTRIBUTE),
+        };
+
+        let offset = quote!(::core::mem::offset_of!(#name, #member));
+
+        let padding_message = format!(
+            "`{}` has padding before field `{}`. Vertex structs must be tightly packed",
+            name, field_name
+        );
+        let size_message = format!(
+            "The size of field `{}` of `{}` doesn't match its vertex attribute format",
+            field_name, name
+        );
+
+        checks.push(quote! {
+            assert!(#offset == #previous_end, #padding_message);
+            assert!(
+                ::core::mem::size_of::<#ty>() == #attribute.size() as usize,
+                #size_message
+            );
+        });
+
+        attributes.push(quote!((#attribute, #offset as u32)));
+        previous_end = quote!(#offset + ::core::mem::size_of::<#ty>());
+    }
+
+    let trailing_message = format!(
+        "`{}` has padding after its last field. Vertex structs must be tightly packed",
+        name
+    );
+
+    Ok(quote! {
+        impl #krate::Vertex for #name {
+            const ATTRIBUTES: &'static [(#krate::VertexAttribute, u32)] = &[#(#attributes),*];
+            const PER_INSTANCE: bool = #per_instance;
+        }
+
+        const _: () = {
+            #(#checks)*
+            assert!(::core::mem::size_of::<#name>() == #previous_end, #trailing_message);
+        };
+    })
+}
+
+fn has_repr_c(input: &DeriveInput) -> syn::Result<bool> {
+    let mut repr_c = false;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("repr"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") {
+                repr_c = true;
+            }
+
+            // Skip the arguments of things like `align(4)`.
+            if meta.input.peek(syn::token::Paren) {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                content.parse::<proc_macro2::TokenStream>()?;
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(repr_c)
+}
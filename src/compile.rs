@@ -0,0 +1,515 @@
+//! Compiling GLSL and WGSL to SPIR-V with naga. Requires the `shader-compilation` feature.
+
+use ash::vk;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// The language of a shader source file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderSourceLanguage {
+    /// A GLSL file contains a single stage, which has to be given.
+    Glsl(vk::ShaderStageFlags),
+    Wgsl,
+}
+
+impl ShaderSourceLanguage {
+    /// Guess the language from a file extension: `.wgsl`, or `.vert`, `.frag` and `.comp` for
+    /// GLSL, optionally followed by `.glsl`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let mut extension = path.extension()?.to_str()?;
+
+        if extension == "glsl" {
+            extension = Path::new(path.file_stem()?).extension()?.to_str()?;
+        }
+
+        Some(match extension {
+            "wgsl" => Self::Wgsl,
+            "vert" => Self::Glsl(vk::ShaderStageFlags::VERTEX),
+            "frag" => Self::Glsl(vk::ShaderStageFlags::FRAGMENT),
+            "comp" => Self::Glsl(vk::ShaderStageFlags::COMPUTE),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct ShaderCompileDescriptor<'a> {
+    /// Guessed from the file extension if `None`. Required when compiling a string.
+    pub language: Option<ShaderSourceLanguage>,
+    /// Directories searched for `#include`s, after the directory of the including file.
+    pub include_dirs: &'a [&'a Path],
+    /// Macro definitions. For GLSL these are passed to the preprocessor; WGSL only supports
+    /// checking them with `#ifdef`, `#ifndef`, `#else` and `#endif`.
+    pub defines: &'a [(&'a str, &'a str)],
+}
+
+/// Compile a GLSL or WGSL file to SPIR-V bytes, as accepted by
+/// [`load_shader_module`](crate::load_shader_module) and
+/// [`ShaderStage::new`](crate::ShaderStage::new).
+pub fn compile_shader_file(
+    path: &Path,
+    descriptor: &ShaderCompileDescriptor,
+) -> anyhow::Result<Vec<u8>> {
+    let language = match descriptor.language {
+        Some(language) => language,
+        None => ShaderSourceLanguage::from_path(path).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Can't tell the shader language of {:?} from its extension",
+                path
+            )
+        })?,
+    };
+
+    let source = read_source(path)?;
+
+    compile(&source, Some(path), language, descriptor)
+}
+
+/// Compile GLSL or WGSL source to SPIR-V bytes. Includes are resolved relative to
+/// `include_dirs` only.
+pub fn compile_shader_source(
+    source: &str,
+    descriptor: &ShaderCompileDescriptor,
+) -> anyhow::Result<Vec<u8>> {
+    let language = descriptor
+        .language
+        .ok_or_else(|| anyhow::anyhow!("A language is required to compile shader source"))?;
+
+    compile(source, None, language, descriptor)
+}
+
+/// Compile a GLSL or WGSL file and load it. naga writes SPIR-V 1.0, which every Vulkan version
+/// accepts, so unlike [`load_shader_module_checked`](crate::load_shader_module_checked) there's
+/// no api version to check against.
+pub fn load_shader_module_from_source(
+    path: &Path,
+    descriptor: &ShaderCompileDescriptor,
+    device: &ash::Device,
+) -> anyhow::Result<vk::ShaderModule> {
+    crate::load_shader_module(&compile_shader_file(path, descriptor)?, device)
+}
+
+fn read_source(path: &Path) -> anyhow::Result<String> {
+    std::fs::read_to_string(path)
+        .map_err(|error| anyhow::anyhow!("Failed to read {:?}: {}", path, error))
+}
+
+fn compile(
+    source: &str,
+    path: Option<&Path>,
+    language: ShaderSourceLanguage,
+    descriptor: &ShaderCompileDescriptor,
+) -> anyhow::Result<Vec<u8>> {
+    let mut preprocessed = Preprocessed::new(descriptor);
+    preprocessed.expand(source, path, language, descriptor, &mut Vec::new())?;
+
+    let module = match language {
+        ShaderSourceLanguage::Glsl(stage) => {
+            let stage = match stage {
+                vk::ShaderStageFlags::VERTEX => naga::ShaderStage::Vertex,
+                vk::ShaderStageFlags::FRAGMENT => naga::ShaderStage::Fragment,
+                vk::ShaderStageFlags::COMPUTE => naga::ShaderStage::Compute,
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "{:?} shaders can't be compiled from GLSL",
+                        stage
+                    ))
+                }
+            };
+
+            let options = naga::front::glsl::Options {
+                stage,
+                defines: descriptor
+                    .defines
+                    .iter()
+                    .map(|&(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+            };
+
+            naga::front::glsl::Frontend::default()
+                .parse(&options, &preprocessed.source)
+                .map_err(|errors| {
+                    let messages: Vec<_> = errors
+                        .errors
+                        .iter()
+                        .map(|error| {
+                            preprocessed.message(
+                                error.location(&preprocessed.source),
+                                &error.kind.to_string(),
+                            )
+                        })
+                        .collect();
+
+                    anyhow::anyhow!("{}", messages.join("\n"))
+                })?
+        }
+        ShaderSourceLanguage::Wgsl => {
+            naga::front::wgsl::parse_str(&preprocessed.source).map_err(|error| {
+                anyhow::anyhow!(
+                    "{}",
+                    preprocessed.message(error.location(&preprocessed.source), error.message())
+                )
+            })?
+        }
+    };
+
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|error| {
+        let mut message = error.as_inner().to_string();
+        let mut source: &dyn std::error::Error = error.as_inner();
+
+        while let Some(next) = source.source() {
+            message = format!("{}: {}", message, next);
+            source = next;
+        }
+
+        anyhow::anyhow!(
+            "{}",
+            preprocessed.message(error.location(&preprocessed.source), &message)
+        )
+    })?;
+
+    let mut options = naga::back::spv::Options::default();
+    // Shaders are written against Vulkan's coordinate conventions, so don't convert from
+    // WebGPU's.
+    options.flags -= naga::back::spv::WriterFlags::ADJUST_COORDINATE_SPACE
+        | naga::back::spv::WriterFlags::CLAMP_FRAG_DEPTH;
+
+    let words = naga::back::spv::write_vec(&module, &info, &options, None)?;
+
+    Ok(words.iter().flat_map(|word| word.to_le_bytes()).collect())
+}
+
+/// Source with includes expanded, along with the original file and line of each line.
+#[derive(Default)]
+struct Preprocessed {
+    source: String,
+    lines: Vec<(Option<PathBuf>, u32)>,
+    /// Macros known to be defined at the current line.
+    defined: HashSet<String>,
+    /// Macros that were defined or undefined inside a GLSL `#if`, so may or may not be defined.
+    uncertain: HashSet<String>,
+}
+
+impl Preprocessed {
+    fn new(descriptor: &ShaderCompileDescriptor) -> Self {
+        Self {
+            defined: descriptor
+                .defines
+                .iter()
+                .map(|&(name, _)| name.to_string())
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn is_defined(&self, name: &str) -> Option<bool> {
+        if self.uncertain.contains(name) {
+            None
+        } else {
+            Some(self.defined.contains(name))
+        }
+    }
+
+    /// Expand includes. WGSL conditionals are resolved here. GLSL ones are left for naga, but
+    /// are tracked as far as possible so that includes in inactive blocks are skipped, as the
+    /// included file may not exist when the block's condition isn't met.
+    fn expand(
+        &mut self,
+        source: &str,
+        path: Option<&Path>,
+        language: ShaderSourceLanguage,
+        descriptor: &ShaderCompileDescriptor,
+        include_stack: &mut Vec<PathBuf>,
+    ) -> anyhow::Result<()> {
+        let is_wgsl = language == ShaderSourceLanguage::Wgsl;
+
+        // Whether each enclosing conditional block is active, or `None` if that depends on a
+        // GLSL `#if` expression.
+        let mut conditions: Vec<Option<bool>> = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index as u32 + 1;
+            let location = || match path {
+                Some(path) => format!("{}:{}", path.display(), line_number),
+                None => format!("<source>:{}", line_number),
+            };
+            let trimmed = line.trim_start();
+            let directive = trimmed.split_whitespace().next().unwrap_or_default();
+            let argument = trimmed.split_whitespace().nth(1);
+
+            let handled = match (directive, argument) {
+                ("#ifdef", Some(name)) | ("#ifndef", Some(name)) => {
+                    let defined = self.is_defined(name);
+                    conditions.push(defined.map(|defined| defined == (directive == "#ifdef")));
+                    true
+                }
+                ("#if", _) if !is_wgsl => {
+                    conditions.push(None);
+                    true
+                }
+                ("#elif", _) if !is_wgsl => {
+                    if let Some(condition) = conditions.last_mut() {
+                        // An earlier branch was taken, so no later one can be.
+                        *condition = match *condition {
+                            Some(true) => Some(false),
+                            _ => None,
+                        };
+                    }
+                    true
+                }
+                ("#else", _) => {
+                    match conditions.last_mut() {
+                        Some(condition) => *condition = condition.map(|active| !active),
+                        None if is_wgsl => {
+                            return Err(anyhow::anyhow!("{}: #else without #ifdef", location()))
+                        }
+                        None => {}
+                    }
+                    true
+                }
+                ("#endif", _) => {
+                    if conditions.pop().is_none() && is_wgsl {
+                        return Err(anyhow::anyhow!("{}: #endif without #ifdef", location()));
+                    }
+                    true
+                }
+                _ => false,
+            };
+
+            let inactive = conditions.contains(&Some(false));
+
+            if !is_wgsl && !inactive {
+                let certain = conditions.iter().all(|&condition| condition == Some(true));
+
+                match (directive, argument) {
+                    ("#define", Some(name)) | ("#undef", Some(name)) => {
+                        // `#define NAME(args) ...` defines `NAME`.
+                        let name = name.split('(').next().unwrap_or(name).to_string();
+
+                        if !certain {
+                            self.uncertain.insert(name);
+                        } else if directive == "#define" {
+                            self.defined.insert(name);
+                        } else {
+                            self.defined.remove(&name);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            if is_wgsl && (handled || inactive) {
+                // Blank out directives and inactive lines so that columns stay meaningful.
+                self.push_line("", path, line_number);
+                continue;
+            }
+
+            if let Some(include) = trimmed.strip_prefix("#include") {
+                if inactive {
+                    self.push_line("", path, line_number);
+                    continue;
+                }
+
+                let name = include
+                    .trim()
+                    .strip_prefix(['"', '<'])
+                    .and_then(|name| name.strip_suffix(['"', '>']))
+                    .ok_or_else(|| anyhow::anyhow!("{}: malformed #include", location()))?;
+
+                let include_path =
+                    find_include(name, path, descriptor.include_dirs).ok_or_else(|| {
+                        anyhow::anyhow!("{}: can't find include {:?}", location(), name)
+                    })?;
+
+                if include_stack.contains(&include_path) {
+                    return Err(anyhow::anyhow!(
+                        "{}: {:?} includes itself",
+                        location(),
+                        include_path
+                    ));
+                }
+
+                let included = read_source(&include_path)?;
+
+                include_stack.push(include_path.clone());
+                self.expand(
+                    &included,
+                    Some(&include_path),
+                    language,
+                    descriptor,
+                    include_stack,
+                )?;
+                include_stack.pop();
+
+                continue;
+            }
+
+            self.push_line(line, path, line_number);
+        }
+
+        if is_wgsl && !conditions.is_empty() {
+            return Err(anyhow::anyhow!(
+                "{}: unterminated #ifdef",
+                path.map_or("<source>".into(), |path| path.display().to_string())
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn push_line(&mut self, line: &str, path: Option<&Path>, line_number: u32) {
+        self.source.push_str(line);
+        self.source.push('\n');
+        self.lines.push((path.map(Path::to_path_buf), line_number));
+    }
+
+    /// Prefix an error message with the original file and line.
+    fn message(&self, location: Option<naga::SourceLocation>, message: &str) -> String {
+        let original = location.and_then(|location| {
+            self.lines
+                .get(location.line_number.checked_sub(1)? as usize)
+                .map(|(path, line)| (path, line, location.line_position))
+        });
+
+        match original {
+            Some((Some(path), line, column)) => {
+                format!("{}:{}:{}: {}", path.display(), line, column, message)
+            }
+            Some((None, line, column)) => format!("<source>:{}:{}: {}", line, column, message),
+            None => message.to_string(),
+        }
+    }
+}
+
+fn find_include(
+    name: &str,
+    including_file: Option<&Path>,
+    include_dirs: &[&Path],
+) -> Option<PathBuf> {
+    including_file
+        .and_then(Path::parent)
+        .into_iter()
+        .chain(include_dirs.iter().copied())
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLSL: ShaderSourceLanguage = ShaderSourceLanguage::Glsl(vk::ShaderStageFlags::COMPUTE);
+
+    fn expand(source: &str, language: ShaderSourceLanguage, defines: &[(&str, &str)]) -> String {
+        let descriptor = ShaderCompileDescriptor {
+            language: Some(language),
+            defines,
+            ..Default::default()
+        };
+
+        let mut preprocessed = Preprocessed::new(&descriptor);
+        preprocessed
+            .expand(source, None, language, &descriptor, &mut Vec::new())
+            .map(|()| preprocessed.source)
+            .unwrap_or_else(|error| error.to_string())
+    }
+
+    #[test]
+    fn skips_glsl_includes_in_inactive_blocks() {
+        let source = "#ifdef USE_EXTRA\n#include \"extra.glsl\"\n#endif\n";
+
+        // The directives are left for naga.
+        assert_eq!(expand(source, GLSL, &[]), "#ifdef USE_EXTRA\n\n#endif\n");
+        assert_eq!(
+            expand(source, GLSL, &[("USE_EXTRA", "1")]),
+            "<source>:2: can't find include \"extra.glsl\""
+        );
+    }
+
+    #[test]
+    fn tracks_glsl_defines_in_the_source() {
+        let source = "#undef NO_EXTRA\n#ifdef NO_EXTRA\n#include \"a\"\n#endif\n";
+
+        assert_eq!(
+            expand(source, GLSL, &[("NO_EXTRA", "")]),
+            "#undef NO_EXTRA\n#ifdef NO_EXTRA\n\n#endif\n"
+        );
+
+        // `a` is skipped, but whether the `#elif` branch is taken can't be told.
+        let source = "#define USE_EXTRA(x) x\n#ifndef USE_EXTRA\n#include \"a\"\n\
+                      #elif VERSION > 2\n#include \"b\"\n#endif\n";
+
+        assert_eq!(
+            expand(source, GLSL, &[]),
+            "<source>:5: can't find include \"b\""
+        );
+    }
+
+    #[test]
+    fn resolves_includes_in_uncertain_glsl_blocks() {
+        let source = "#if defined(A) && defined(B)\n#include \"missing.glsl\"\n#endif\n";
+
+        assert_eq!(
+            expand(source, GLSL, &[]),
+            "<source>:2: can't find include \"missing.glsl\""
+        );
+    }
+
+    #[test]
+    fn resolves_wgsl_conditionals() {
+        let source = "#ifdef A\na\n#else\nnot a\n#endif\n#ifndef B\nnot b\n#endif\n";
+
+        assert_eq!(
+            expand(source, ShaderSourceLanguage::Wgsl, &[("A", "")]),
+            "\na\n\n\n\n\nnot b\n\n"
+        );
+        assert_eq!(
+            expand("#ifdef A\n", ShaderSourceLanguage::Wgsl, &[]),
+            "<source>: unterminated #ifdef"
+        );
+        assert_eq!(
+            expand("#endif\n", ShaderSourceLanguage::Wgsl, &[]),
+            "<source>:1: #endif without #ifdef"
+        );
+    }
+
+    #[test]
+    fn compiles_glsl_with_an_inactive_missing_include() {
+        let source = "#version 450\n\
+                      #ifdef USE_EXTRA\n#include \"extra.glsl\"\n#endif\n\
+                      layout(local_size_x = 1) in;\nvoid main() {}\n";
+
+        let bytes = compile_shader_source(
+            source,
+            &ShaderCompileDescriptor {
+                language: Some(GLSL),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert!(crate::read_spirv(&bytes).is_ok());
+    }
+
+    #[test]
+    fn writes_spirv_1_0() {
+        let bytes = compile_shader_source(
+            "@compute @workgroup_size(1) fn main() {}",
+            &ShaderCompileDescriptor {
+                language: Some(ShaderSourceLanguage::Wgsl),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let info = crate::SpirvModuleInfo::parse(&crate::read_spirv(&bytes).unwrap()).unwrap();
+
+        assert_eq!(info.version, (1, 0));
+        assert!(info.check_api_version(vk::API_VERSION_1_0).is_ok());
+    }
+}
@@ -0,0 +1,566 @@
+use ash::vk;
+use std::ffi::CStr;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Changes the log level of messages with a given type and severity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeverityOverride {
+    pub message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    pub severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub level: log::Level,
+}
+
+/// Settings for [`vulkan_debug_utils_callback`], passed to it as user data.
+///
+/// The defaults downgrade general verbose and info messages and validation info messages to
+/// `trace`.
+///
+/// Validation errors that aren't muted are counted, so that tests can fail on them with
+/// [`Self::check_no_validation_errors`]. Clones share the same count.
+#[derive(Clone, Debug)]
+pub struct DebugMessengerSettings {
+    /// `message_id_number`s of messages to drop entirely.
+    pub muted_message_ids: Vec<i32>,
+    /// Message ID names, such as VUIDs, of messages to drop entirely.
+    pub muted_message_names: Vec<String>,
+    /// The first matching override is used. Messages without one are logged at the level
+    /// matching their severity.
+    pub severity_overrides: Vec<SeverityOverride>,
+    /// Stores every message that isn't muted, in addition to logging it.
+    pub collector: Option<DebugMessageCollector>,
+    validation_errors: Arc<AtomicUsize>,
+}
+
+impl Default for DebugMessengerSettings {
+    fn default() -> Self {
+        let downgrade = |message_type, severity| SeverityOverride {
+            message_type,
+            severity,
+            level: log::Level::Trace,
+        };
+
+        Self {
+            muted_message_ids: Vec::new(),
+            muted_message_names: Vec::new(),
+            severity_overrides: vec![
+                downgrade(
+                    vk::DebugUtilsMessageTypeFlagsEXT::GENERAL,
+                    vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+                ),
+                downgrade(
+                    vk::DebugUtilsMessageTypeFlagsEXT::GENERAL,
+                    vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+                ),
+                downgrade(
+                    vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+                    vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+                ),
+            ],
+            collector: None,
+            validation_errors: Arc::default(),
+        }
+    }
+}
+
+impl DebugMessengerSettings {
+    pub fn mute_message_id(mut self, message_id_number: i32) -> Self {
+        self.muted_message_ids.push(message_id_number);
+        self
+    }
+
+    pub fn mute_message_name(mut self, message_id_name: &str) -> Self {
+        self.muted_message_names.push(message_id_name.to_string());
+        self
+    }
+
+    /// Overrides added later take precedence over earlier ones, including the defaults.
+    pub fn override_severity(
+        mut self,
+        message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+        severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+        level: log::Level,
+    ) -> Self {
+        self.severity_overrides.insert(
+            0,
+            SeverityOverride {
+                message_type,
+                severity,
+                level,
+            },
+        );
+        self
+    }
+
+    pub fn capture(mut self, collector: &DebugMessageCollector) -> Self {
+        self.collector = Some(collector.clone());
+        self
+    }
+
+    /// The number of validation errors reported since the last
+    /// [`check_no_validation_errors`](Self::check_no_validation_errors).
+    pub fn validation_error_count(&self) -> usize {
+        self.validation_errors.load(Ordering::Relaxed)
+    }
+
+    /// Fail if any validation errors were reported since the last check, and reset the count.
+    ///
+    /// The callback can't panic or return an error itself, as it's called from inside the
+    /// driver, so call this after the work that's being tested instead. The messages themselves
+    /// are logged, or can be captured with a [`DebugMessageCollector`].
+    pub fn check_no_validation_errors(&self) -> anyhow::Result<()> {
+        match self.validation_errors.swap(0, Ordering::Relaxed) {
+            0 => Ok(()),
+            count => Err(anyhow::anyhow!(
+                "The debug messenger reported {} validation error(s)",
+                count
+            )),
+        }
+    }
+
+    fn is_muted(&self, message_id_number: i32, message_id_name: Option<&str>) -> bool {
+        self.muted_message_ids.contains(&message_id_number)
+            || message_id_name.is_some_and(|name| {
+                self.muted_message_names
+                    .iter()
+                    .any(|muted| muted.as_str() == name)
+            })
+    }
+
+    fn level(
+        &self,
+        message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+        severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    ) -> log::Level {
+        let severity_override = self.severity_overrides.iter().find(|severity_override| {
+            severity_override.severity == severity
+                && severity_override.message_type.intersects(message_type)
+        });
+
+        match severity_override {
+            Some(severity_override) => severity_override.level,
+            None => match severity {
+                vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE => log::Level::Debug,
+                vk::DebugUtilsMessageSeverityFlagsEXT::INFO => log::Level::Info,
+                vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => log::Level::Warn,
+                vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => log::Level::Error,
+                _ => log::Level::Info,
+            },
+        }
+    }
+}
+
+/// An object referenced by a [`DebugMessage`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugObject {
+    pub object_type: vk::ObjectType,
+    pub handle: u64,
+    /// The name given with [`set_object_name`](crate::set_object_name), if any.
+    pub name: Option<String>,
+}
+
+/// A message captured by a [`DebugMessageCollector`].
+#[derive(Clone, Debug)]
+pub struct DebugMessage {
+    pub severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    /// The VUID for validation messages.
+    pub message_id_name: Option<String>,
+    pub message_id_number: i32,
+    pub message: String,
+    pub objects: Vec<DebugObject>,
+    /// The queue labels that were active, outermost first.
+    pub queue_labels: Vec<String>,
+    /// The command buffer labels that were active, outermost first.
+    pub command_buffer_labels: Vec<String>,
+}
+
+impl DebugMessage {
+    unsafe fn new(
+        severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+        message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+        callback_data: &vk::DebugUtilsMessengerCallbackDataEXT,
+        message_id_name: Option<&str>,
+        message: &CStr,
+    ) -> Self {
+        let objects = raw_slice(callback_data.p_objects, callback_data.object_count)
+            .iter()
+            .map(|object| DebugObject {
+                object_type: object.object_type,
+                handle: object.object_handle,
+                name: optional_cstr(object.p_object_name)
+                    .map(|name| name.to_string_lossy().into_owned()),
+            })
+            .collect();
+
+        Self {
+            severity,
+            message_type,
+            message_id_name: message_id_name.map(str::to_string),
+            message_id_number: callback_data.message_id_number,
+            message: message.to_string_lossy().into_owned(),
+            objects,
+            queue_labels: label_names(
+                callback_data.p_queue_labels,
+                callback_data.queue_label_count,
+            ),
+            command_buffer_labels: label_names(
+                callback_data.p_cmd_buf_labels,
+                callback_data.cmd_buf_label_count,
+            ),
+        }
+    }
+
+    pub fn is_validation_error(&self) -> bool {
+        is_validation_error(self.message_type, self.severity)
+    }
+}
+
+/// Whether a message counts towards [`DebugMessengerSettings::check_no_validation_errors`].
+fn is_validation_error(
+    message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+) -> bool {
+    message_type.contains(vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION)
+        && severity == vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+}
+
+impl fmt::Display for DebugMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{:?}][{:?}]", self.severity, self.message_type)?;
+
+        if let Some(message_id_name) = &self.message_id_name {
+            write!(f, "[{}]", message_id_name)?;
+        }
+
+        write!(f, " {}", self.message)?;
+
+        for object in &self.objects {
+            write!(
+                f,
+                "\n    object: {:?} {:#x}",
+                object.object_type, object.handle
+            )?;
+
+            if let Some(name) = &object.name {
+                write!(f, " {:?}", name)?;
+            }
+        }
+
+        if !self.queue_labels.is_empty() {
+            write!(f, "\n    queue labels: {}", self.queue_labels.join(" > "))?;
+        }
+
+        if !self.command_buffer_labels.is_empty() {
+            write!(
+                f,
+                "\n    command buffer labels: {}",
+                self.command_buffer_labels.join(" > ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A thread-safe store for messages from the debug messenger, shared with it through
+/// [`DebugMessengerSettings::capture`]. Clones share the same messages.
+#[derive(Clone, Debug, Default)]
+pub struct DebugMessageCollector {
+    messages: Arc<Mutex<Vec<DebugMessage>>>,
+}
+
+impl DebugMessageCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, message: DebugMessage) {
+        self.lock().push(message);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<DebugMessage>> {
+        // A panic while the lock is held can't leave the messages in an inconsistent state.
+        self.messages
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// A copy of the messages captured so far.
+    pub fn messages(&self) -> Vec<DebugMessage> {
+        self.lock().clone()
+    }
+
+    /// Remove and return the messages captured so far.
+    pub fn take(&self) -> Vec<DebugMessage> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    pub fn validation_errors(&self) -> Vec<DebugMessage> {
+        self.lock()
+            .iter()
+            .filter(|message| message.is_validation_error())
+            .cloned()
+            .collect()
+    }
+
+    /// Fail with every captured validation error, including its objects and labels.
+    pub fn check_no_validation_errors(&self) -> anyhow::Result<()> {
+        let errors = self.validation_errors();
+
+        if errors.is_empty() {
+            return Ok(());
+        }
+
+        let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+
+        Err(anyhow::anyhow!(
+            "{} validation error(s):\n{}",
+            errors.len(),
+            errors.join("\n")
+        ))
+    }
+}
+
+unsafe fn raw_slice<'a, T>(pointer: *const T, count: u32) -> &'a [T] {
+    if pointer.is_null() || count == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(pointer, count as usize)
+    }
+}
+
+unsafe fn label_names(labels: *const vk::DebugUtilsLabelEXT, count: u32) -> Vec<String> {
+    raw_slice(labels, count)
+        .iter()
+        .filter_map(|label| optional_cstr(label.p_label_name))
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect()
+}
+
+unsafe fn optional_cstr<'a>(pointer: *const std::os::raw::c_char) -> Option<&'a CStr> {
+    if pointer.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(pointer))
+    }
+}
+
+/// A callback for the [Vulkan Debug Utils Messenger](https://docs.rs/ash/0.33.3+1.2.191/ash/vk/struct.DebugUtilsMessengerEXT.html)
+///
+/// If `p_user_data` is not null, it must point to a [`DebugMessengerSettings`] that outlives
+/// the messenger. Otherwise the default settings are used.
+///
+/// # Safety
+///
+/// Don't use this in any way except as an input to [`DebugUtilsMessengerCreateInfoEXTBuilder.pfn_user_callback`](https://docs.rs/ash/0.33.3+1.2.191/ash/vk/struct.DebugUtilsMessengerCreateInfoEXTBuilder.html#method.pfn_user_callback).
+///
+pub unsafe extern "system" fn vulkan_debug_utils_callback(
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
+    p_user_data: *mut std::ffi::c_void,
+) -> vk::Bool32 {
+    static DEFAULT_SETTINGS: OnceLock<DebugMessengerSettings> = OnceLock::new();
+
+    let settings = if p_user_data.is_null() {
+        DEFAULT_SETTINGS.get_or_init(DebugMessengerSettings::default)
+    } else {
+        &*(p_user_data as *const DebugMessengerSettings)
+    };
+
+    let callback_data = &*p_callback_data;
+    let message_id_name = optional_cstr(callback_data.p_message_id_name);
+    let message_id_name = message_id_name.map(|name| name.to_string_lossy());
+
+    if settings.is_muted(callback_data.message_id_number, message_id_name.as_deref()) {
+        return vk::FALSE;
+    }
+
+    let level = settings.level(message_type, message_severity);
+
+    let message = optional_cstr(callback_data.p_message).unwrap_or_default();
+
+    if is_validation_error(message_type, message_severity) {
+        settings.validation_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    if let Some(collector) = &settings.collector {
+        collector.push(DebugMessage::new(
+            message_severity,
+            message_type,
+            callback_data,
+            message_id_name.as_deref(),
+            message,
+        ));
+    }
+
+    let ty = format!("{:?}", message_type).to_lowercase();
+    log::log!(level, "[Debug Msg][{}] {:?}", ty, message);
+
+    vk::FALSE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send(
+        settings: &DebugMessengerSettings,
+        message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+        severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+        message_id_name: &CStr,
+    ) {
+        let callback_data = vk::DebugUtilsMessengerCallbackDataEXT::builder()
+            .message_id_name(message_id_name)
+            .message(c"Something went wrong");
+
+        unsafe {
+            vulkan_debug_utils_callback(
+                severity,
+                message_type,
+                &*callback_data,
+                settings as *const DebugMessengerSettings as *mut std::ffi::c_void,
+            );
+        }
+    }
+
+    #[test]
+    fn counts_validation_errors() {
+        let collector = DebugMessageCollector::new();
+        let settings = DebugMessengerSettings::default()
+            .mute_message_name("VUID-muted")
+            .capture(&collector);
+        let vuid = c"VUID-reported";
+        let muted = c"VUID-muted";
+
+        send(
+            &settings,
+            vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+            vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+            vuid,
+        );
+        send(
+            &settings,
+            vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            muted,
+        );
+        assert!(settings.check_no_validation_errors().is_ok());
+
+        send(
+            &settings,
+            vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            vuid,
+        );
+        assert_eq!(settings.validation_error_count(), 1);
+        assert!(settings.clone().check_no_validation_errors().is_err());
+        assert_eq!(settings.validation_error_count(), 0);
+
+        let errors = collector.validation_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message_id_name.as_deref(), Some("VUID-reported"));
+        assert_eq!(errors[0].message, "Something went wrong");
+        assert!(collector.check_no_validation_errors().is_err());
+        assert_eq!(collector.messages().len(), 2);
+    }
+
+    #[test]
+    fn null_user_data_uses_the_default_settings() {
+        let callback_data = vk::DebugUtilsMessengerCallbackDataEXT::builder().message(c"Hello");
+
+        for _ in 0..2 {
+            let result = unsafe {
+                vulkan_debug_utils_callback(
+                    vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+                    vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+                    &*callback_data,
+                    std::ptr::null_mut(),
+                )
+            };
+            assert_eq!(result, vk::FALSE);
+        }
+    }
+
+    #[test]
+    fn captures_objects_and_labels() {
+        let collector = DebugMessageCollector::new();
+        let settings = DebugMessengerSettings::default().capture(&collector);
+
+        let objects = [
+            *vk::DebugUtilsObjectNameInfoEXT::builder()
+                .object_type(vk::ObjectType::BUFFER)
+                .object_handle(0x1234)
+                .object_name(c"vertices"),
+            *vk::DebugUtilsObjectNameInfoEXT::builder()
+                .object_type(vk::ObjectType::IMAGE)
+                .object_handle(0xabcd),
+        ];
+        let queue_labels = [*vk::DebugUtilsLabelEXT::builder().label_name(c"frame 1")];
+        let command_buffer_labels = [
+            *vk::DebugUtilsLabelEXT::builder().label_name(c"shadows"),
+            *vk::DebugUtilsLabelEXT::builder().label_name(c"cascade 0"),
+        ];
+
+        let callback_data = vk::DebugUtilsMessengerCallbackDataEXT::builder()
+            .message_id_name(c"VUID-vkCmdDraw-None-02699")
+            .message_id_number(42)
+            .message(c"Descriptor set is invalid")
+            .objects(&objects)
+            .queue_labels(&queue_labels)
+            .cmd_buf_labels(&command_buffer_labels);
+
+        unsafe {
+            vulkan_debug_utils_callback(
+                vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+                vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+                &*callback_data,
+                &settings as *const DebugMessengerSettings as *mut std::ffi::c_void,
+            );
+        }
+
+        let messages = collector.take();
+        assert_eq!(messages.len(), 1);
+
+        let message = &messages[0];
+        assert!(message.is_validation_error());
+        assert_eq!(
+            message.message_id_name.as_deref(),
+            Some("VUID-vkCmdDraw-None-02699")
+        );
+        assert_eq!(message.message_id_number, 42);
+        assert_eq!(message.message, "Descriptor set is invalid");
+        assert_eq!(
+            message.objects,
+            [
+                DebugObject {
+                    object_type: vk::ObjectType::BUFFER,
+                    handle: 0x1234,
+                    name: Some("vertices".to_string()),
+                },
+                DebugObject {
+                    object_type: vk::ObjectType::IMAGE,
+                    handle: 0xabcd,
+                    name: None,
+                },
+            ]
+        );
+        assert_eq!(message.queue_labels, ["frame 1"]);
+        assert_eq!(message.command_buffer_labels, ["shadows", "cascade 0"]);
+
+        assert_eq!(
+            message.to_string(),
+            "[ERROR][VALIDATION][VUID-vkCmdDraw-None-02699] Descriptor set is invalid\n    \
+             object: BUFFER 0x1234 \"vertices\"\n    \
+             object: IMAGE 0xabcd\n    \
+             queue labels: frame 1\n    \
+             command buffer labels: shadows > cascade 0"
+        );
+    }
+}
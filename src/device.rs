@@ -0,0 +1,272 @@
+use crate::instance::cstr;
+use crate::requirements::format_api_version;
+use crate::{
+    cstr_from_array, set_object_name, DeviceRequirements, InstanceBundle, QueueFamilies,
+    QueueFamily, SelectedPhysicalDevice,
+};
+use ash::extensions::ext::DebugUtils as DebugUtilsLoader;
+use ash::extensions::khr::Swapchain as SwapchainLoader;
+use ash::vk;
+use gpu_allocator::vulkan::{Allocator, AllocatorCreateDesc};
+use std::ffi::CStr;
+use std::mem::ManuallyDrop;
+use std::os::raw::c_char;
+
+const PORTABILITY_SUBSET_EXTENSION: &[u8] = b"VK_KHR_portability_subset\0";
+
+/// A builder for a [`DeviceBundle`] from the result of physical device selection.
+pub struct DeviceBuilder<'a> {
+    selected: &'a SelectedPhysicalDevice,
+    extensions: Vec<&'a CStr>,
+    features: DeviceRequirements,
+}
+
+impl<'a> DeviceBuilder<'a> {
+    /// The optional extensions that the device supports are enabled automatically, as is
+    /// `VK_KHR_portability_subset`, which portability implementations such as MoltenVK require.
+    pub fn new(selected: &'a SelectedPhysicalDevice) -> Self {
+        Self {
+            selected,
+            extensions: selected.enabled_optional_extensions.iter().collect(),
+            features: DeviceRequirements::default(),
+        }
+    }
+
+    pub fn extensions(mut self, extensions: &[&'a CStr]) -> Self {
+        for &extension in extensions {
+            if !self.extensions.contains(&extension) {
+                self.extensions.push(extension);
+            }
+        }
+
+        self
+    }
+
+    /// Enable the features from a set of requirements. This is typically the same set that was
+    /// used for selection. The api version and limits are ignored.
+    pub fn features(mut self, requirements: &DeviceRequirements) -> Self {
+        self.features = *requirements;
+        self
+    }
+
+    /// Create the device, its queues and loaders, and an allocator.
+    ///
+    /// `instance` must outlive the returned bundle. Fails if Vulkan 1.1 or 1.2 features were
+    /// requested and either the instance or the device is older than Vulkan 1.2.
+    pub fn build(self, instance: &InstanceBundle) -> anyhow::Result<DeviceBundle> {
+        // The 1.1 and 1.2 structs can only be chained on 1.2 instances and devices.
+        if self.features.requests_vulkan_1_2_structs() {
+            for (name, api_version) in [
+                ("instance", instance.api_version),
+                ("device", self.selected.properties.api_version),
+            ] {
+                if api_version < vk::API_VERSION_1_2 {
+                    return Err(anyhow::anyhow!(
+                        "Vulkan 1.1 and 1.2 features need a Vulkan 1.2 {}, but the {} is {}",
+                        name,
+                        name,
+                        format_api_version(api_version)
+                    ));
+                }
+            }
+        }
+
+        let queue_families = self.selected.queue_families;
+
+        // Give each role its own queue where the family has enough of them.
+        let mut queue_counts: Vec<(u32, u32)> = Vec::with_capacity(3);
+
+        let mut assign_queue = |family: QueueFamily| {
+            let count = match queue_counts
+                .iter_mut()
+                .find(|(index, _)| *index == family.index)
+            {
+                Some((_, count)) => count,
+                None => {
+                    queue_counts.push((family.index, 0));
+                    &mut queue_counts.last_mut().unwrap().1
+                }
+            };
+
+            let queue_index = (*count).min(family.queue_count - 1);
+            *count = (*count + 1).min(family.queue_count);
+
+            (family.index, queue_index)
+        };
+
+        let graphics_queue = assign_queue(queue_families.graphics);
+        let compute_queue = assign_queue(queue_families.compute);
+        let transfer_queue = assign_queue(queue_families.transfer);
+
+        let queue_priorities: Vec<Vec<f32>> = queue_counts
+            .iter()
+            .map(|&(_, count)| vec![1.0; count as usize])
+            .collect();
+
+        let queue_infos: Vec<_> = queue_counts
+            .iter()
+            .zip(&queue_priorities)
+            .map(|(&(index, _), priorities)| {
+                *vk::DeviceQueueCreateInfo::builder()
+                    .queue_family_index(index)
+                    .queue_priorities(priorities)
+            })
+            .collect();
+
+        let mut extensions = self.extensions;
+
+        let available_extensions = unsafe {
+            instance
+                .instance
+                .enumerate_device_extension_properties(self.selected.physical_device)
+        }?;
+
+        let portability_subset = cstr(PORTABILITY_SUBSET_EXTENSION);
+
+        if !extensions.contains(&portability_subset)
+            && available_extensions.iter().any(|extension| {
+                let name = unsafe { cstr_from_array(&extension.extension_name) };
+                name == portability_subset
+            })
+        {
+            extensions.push(portability_subset);
+        }
+
+        let extension_pointers: Vec<*const c_char> = extensions
+            .iter()
+            .map(|extension| extension.as_ptr())
+            .collect();
+
+        // Copy the feature structs so that we control their `p_next` pointers.
+        let mut vulkan_1_1_features = vk::PhysicalDeviceVulkan11Features {
+            p_next: std::ptr::null_mut(),
+            ..self.features.vulkan_1_1_features
+        };
+
+        let mut vulkan_1_2_features = vk::PhysicalDeviceVulkan12Features {
+            p_next: std::ptr::null_mut(),
+            ..self.features.vulkan_1_2_features
+        };
+
+        let mut features = vk::PhysicalDeviceFeatures2::builder()
+            .features(self.features.features)
+            .push_next(&mut vulkan_1_1_features)
+            .push_next(&mut vulkan_1_2_features);
+
+        let mut device_info = vk::DeviceCreateInfo::builder()
+            .queue_create_infos(&queue_infos)
+            .enabled_extension_names(&extension_pointers);
+
+        device_info = if self.features.requests_vulkan_1_2_structs() {
+            device_info.push_next(&mut features)
+        } else {
+            device_info.enabled_features(&self.features.features)
+        };
+
+        let device = unsafe {
+            instance
+                .instance
+                .create_device(self.selected.physical_device, &device_info, None)
+        }?;
+
+        let get_queue = |(family_index, queue_index)| unsafe {
+            device.get_device_queue(family_index, queue_index)
+        };
+
+        let queues = Queues {
+            graphics: get_queue(graphics_queue),
+            compute: get_queue(compute_queue),
+            transfer: get_queue(transfer_queue),
+        };
+
+        let debug_utils_loader = instance.debug_utils_loader.clone();
+
+        if let Some(debug_utils_loader) = &debug_utils_loader {
+            let mut named_queues = Vec::with_capacity(3);
+
+            for (queue, name) in [
+                (queues.graphics, "graphics queue"),
+                (queues.compute, "compute queue"),
+                (queues.transfer, "transfer queue"),
+            ] {
+                // Roles can share a queue, which keeps the name of the first role.
+                if named_queues.contains(&queue) {
+                    continue;
+                }
+
+                named_queues.push(queue);
+
+                // Names are only for debugging, so failing to set one isn't worth losing the
+                // device over.
+                if let Err(error) = set_object_name(&device, debug_utils_loader, queue, name) {
+                    log::warn!("Failed to name the {}: {}", name, error);
+                }
+            }
+        }
+
+        let swapchain_loader = if extensions.contains(&SwapchainLoader::name()) {
+            Some(SwapchainLoader::new(&instance.instance, &device))
+        } else {
+            None
+        };
+
+        let allocator = Allocator::new(&AllocatorCreateDesc {
+            instance: instance.instance.clone(),
+            device: device.clone(),
+            physical_device: self.selected.physical_device,
+            debug_settings: Default::default(),
+            buffer_device_address: self.features.vulkan_1_2_features.buffer_device_address
+                == vk::TRUE,
+        });
+
+        let allocator = match allocator {
+            Ok(allocator) => allocator,
+            Err(error) => {
+                unsafe { device.destroy_device(None) };
+                return Err(error.into());
+            }
+        };
+
+        Ok(DeviceBundle {
+            physical_device: self.selected.physical_device,
+            queue_families,
+            queues,
+            swapchain_loader,
+            debug_utils_loader,
+            allocator: ManuallyDrop::new(allocator),
+            device,
+        })
+    }
+}
+
+/// The queues for each role. These may be the same queue if the device doesn't have enough
+/// queues, in which case submissions need to be synchronised externally.
+#[derive(Clone, Copy, Debug)]
+pub struct Queues {
+    pub graphics: vk::Queue,
+    pub compute: vk::Queue,
+    pub transfer: vk::Queue,
+}
+
+/// A logical device along with its queues, loaders and allocator. Frees the allocator and then
+/// destroys the device when dropped.
+pub struct DeviceBundle {
+    pub device: ash::Device,
+    pub physical_device: vk::PhysicalDevice,
+    pub queue_families: QueueFamilies,
+    pub queues: Queues,
+    /// Only present if `VK_KHR_swapchain` was enabled.
+    pub swapchain_loader: Option<SwapchainLoader>,
+    /// Only present if `VK_EXT_debug_utils` was enabled on the instance.
+    pub debug_utils_loader: Option<DebugUtilsLoader>,
+    pub allocator: ManuallyDrop<Allocator>,
+}
+
+impl Drop for DeviceBundle {
+    fn drop(&mut self) {
+        unsafe {
+            ManuallyDrop::drop(&mut self.allocator);
+            self.device.destroy_device(None);
+        }
+    }
+}
@@ -0,0 +1,521 @@
+use crate::{GraphicsPipelineDescriptor, ShaderStage, SpecializationConstants};
+use ash::extensions::ext::DebugUtils as DebugUtilsLoader;
+use ash::vk;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+/// A handle to a file tracked by a [`ShaderWatcher`]. Only valid for the watcher that returned
+/// it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WatchedShaderId {
+    watcher: usize,
+    index: usize,
+}
+
+/// A watched file that has changed, along with its new contents.
+pub struct ShaderChange {
+    pub id: WatchedShaderId,
+    pub bytes: Vec<u8>,
+}
+
+struct WatchedShader {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+/// Tracks the modification times of `.spv` files so that they can be reloaded while the
+/// application is running. Nothing is watched unless a watcher is created and polled.
+pub struct ShaderWatcher {
+    /// Distinguishes the ids of different watchers.
+    id: usize,
+    shaders: Vec<WatchedShader>,
+}
+
+impl Default for ShaderWatcher {
+    fn default() -> Self {
+        static NEXT_WATCHER_ID: AtomicUsize = AtomicUsize::new(0);
+
+        Self {
+            id: NEXT_WATCHER_ID.fetch_add(1, Ordering::Relaxed),
+            shaders: Vec::new(),
+        }
+    }
+}
+
+impl ShaderWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start watching a file. Watching the same path twice returns the same id.
+    pub fn watch(&mut self, path: impl Into<PathBuf>) -> WatchedShaderId {
+        let path = path.into();
+
+        let index = match self.shaders.iter().position(|shader| shader.path == path) {
+            Some(index) => index,
+            None => {
+                self.shaders.push(WatchedShader {
+                    modified: modified_time(&path),
+                    path,
+                });
+
+                self.shaders.len() - 1
+            }
+        };
+
+        self.id(index)
+    }
+
+    /// Fails if `id` came from a different watcher.
+    pub fn path(&self, id: WatchedShaderId) -> anyhow::Result<&Path> {
+        match self.shaders.get(id.index) {
+            Some(shader) if id.watcher == self.id => Ok(&shader.path),
+            _ => Err(anyhow::anyhow!(
+                "{:?} was not returned by this shader watcher",
+                id
+            )),
+        }
+    }
+
+    /// Read the current contents of a watched file.
+    pub fn read(&self, id: WatchedShaderId) -> anyhow::Result<Vec<u8>> {
+        let path = self.path(id)?;
+
+        std::fs::read(path).map_err(|error| anyhow::anyhow!("Failed to read {:?}: {}", path, error))
+    }
+
+    /// Check every watched file and return the ones that have changed since the last poll.
+    ///
+    /// A file that can't be read or isn't valid SPIR-V yet, for example because a compiler is
+    /// still writing it, is skipped and checked again on the next poll.
+    pub fn poll(&mut self) -> Vec<ShaderChange> {
+        let watcher = self.id;
+        let mut changes = Vec::new();
+
+        for (index, shader) in self.shaders.iter_mut().enumerate() {
+            let modified = modified_time(&shader.path);
+
+            if modified.is_none() || modified == shader.modified {
+                continue;
+            }
+
+            let bytes = match std::fs::read(&shader.path) {
+                Ok(bytes) => bytes,
+                Err(_) => continue,
+            };
+
+            if crate::read_spirv(&bytes).is_err() {
+                continue;
+            }
+
+            log::info!("Reloading {:?}", shader.path);
+
+            shader.modified = modified;
+
+            changes.push(ShaderChange {
+                id: WatchedShaderId { watcher, index },
+                bytes,
+            });
+        }
+
+        changes
+    }
+
+    fn id(&self, index: usize) -> WatchedShaderId {
+        WatchedShaderId {
+            watcher: self.id,
+            index,
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// The shaders of a [`ReloadablePipeline`]. `entry_points` are as in
+/// [`ShaderStage::with_entry_points`].
+pub struct WatchedShaderDescriptor<'a> {
+    pub id: WatchedShaderId,
+    pub entry_points: &'a [(vk::ShaderStageFlags, &'a str)],
+    /// Kept across reloads.
+    pub specialization: Option<&'a SpecializationConstants>,
+    /// If set, the SPIR-V version of the shader is checked against this api version every time
+    /// it's loaded, as in [`ShaderStage::with_entry_points_checked`].
+    pub api_version: Option<u32>,
+}
+
+struct WatchedStage {
+    id: WatchedShaderId,
+    api_version: Option<u32>,
+    stage: ShaderStage,
+}
+
+/// A graphics pipeline that is recreated when any of its shaders change on disk.
+///
+/// Replaced pipelines are kept until the frames that might be using them have completed. Frames
+/// are identified by a counter that the application increments for every frame it records.
+pub struct ReloadablePipeline {
+    pub pipeline: vk::Pipeline,
+    pub layout: vk::PipelineLayout,
+    pub render_pass: vk::RenderPass,
+    pub subpass: u32,
+    stages: Vec<WatchedStage>,
+    retired: Vec<(u64, vk::Pipeline)>,
+}
+
+impl ReloadablePipeline {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        watcher: &ShaderWatcher,
+        shaders: &[WatchedShaderDescriptor],
+        descriptor: &GraphicsPipelineDescriptor,
+        layout: vk::PipelineLayout,
+        render_pass: vk::RenderPass,
+        subpass: u32,
+        device: &ash::Device,
+        debug_utils_loader: Option<&DebugUtilsLoader>,
+    ) -> anyhow::Result<Self> {
+        let mut stages = Vec::with_capacity(shaders.len());
+
+        for shader in shaders {
+            let stage = watcher.read(shader.id).and_then(|bytes| {
+                load_stage(
+                    watcher,
+                    shader.id,
+                    &bytes,
+                    shader.entry_points,
+                    shader.api_version,
+                    device,
+                    debug_utils_loader,
+                )
+            });
+
+            match stage {
+                Ok(stage) => stages.push(WatchedStage {
+                    id: shader.id,
+                    api_version: shader.api_version,
+                    stage: match shader.specialization {
+                        Some(constants) => stage.specialize(constants.clone()),
+                        None => stage,
+                    },
+                }),
+                Err(error) => {
+                    for watched in &stages {
+                        watched.stage.cleanup(device);
+                    }
+
+                    return Err(error);
+                }
+            }
+        }
+
+        let mut pipeline = Self {
+            pipeline: vk::Pipeline::null(),
+            layout,
+            render_pass,
+            subpass,
+            stages,
+            retired: Vec::new(),
+        };
+
+        match pipeline.create_pipeline(descriptor, device) {
+            Ok(handle) => pipeline.pipeline = handle,
+            Err(error) => {
+                pipeline.cleanup(device);
+                return Err(error);
+            }
+        }
+
+        Ok(pipeline)
+    }
+
+    /// Recreate the pipeline if any of its shaders are in `changes`. Returns whether the
+    /// pipeline was replaced.
+    ///
+    /// `frame` is the frame currently being recorded. The old pipeline is retired until
+    /// [`Self::destroy_retired`] is called with a completed frame at least that recent. If any
+    /// shader fails to load or the pipeline fails to compile, the error is returned and the old
+    /// pipeline is kept.
+    pub fn update(
+        &mut self,
+        watcher: &ShaderWatcher,
+        changes: &[ShaderChange],
+        descriptor: &GraphicsPipelineDescriptor,
+        frame: u64,
+        device: &ash::Device,
+        debug_utils_loader: Option<&DebugUtilsLoader>,
+    ) -> anyhow::Result<bool> {
+        let mut new_stages = Vec::new();
+
+        for (index, watched) in self.stages.iter().enumerate() {
+            let change = match changes.iter().find(|change| change.id == watched.id) {
+                Some(change) => change,
+                None => continue,
+            };
+
+            let entry_points: Vec<_> = watched.stage.entry_points().collect();
+
+            match load_stage(
+                watcher,
+                watched.id,
+                &change.bytes,
+                &entry_points,
+                watched.api_version,
+                device,
+                debug_utils_loader,
+            ) {
+                Ok(new_stage) => new_stages.push((
+                    index,
+                    new_stage.specialize(watched.stage.specialization().clone()),
+                )),
+                Err(error) => {
+                    for (_, stage) in &new_stages {
+                        stage.cleanup(device);
+                    }
+
+                    return Err(error);
+                }
+            }
+        }
+
+        if new_stages.is_empty() {
+            return Ok(false);
+        }
+
+        // Swap the new stages in, leaving the old ones in `new_stages`.
+        for (index, stage) in &mut new_stages {
+            std::mem::swap(&mut self.stages[*index].stage, stage);
+        }
+
+        match self.create_pipeline(descriptor, device) {
+            Ok(pipeline) => {
+                self.retired.push((frame, self.pipeline));
+                self.pipeline = pipeline;
+
+                // Shader modules aren't needed once the pipeline has been created.
+                for (_, stage) in &new_stages {
+                    stage.cleanup(device);
+                }
+
+                Ok(true)
+            }
+            Err(error) => {
+                for (index, stage) in &mut new_stages {
+                    std::mem::swap(&mut self.stages[*index].stage, stage);
+                    stage.cleanup(device);
+                }
+
+                Err(error)
+            }
+        }
+    }
+
+    /// Destroy the pipelines that were replaced at or before `completed_frame`.
+    pub fn destroy_retired(&mut self, device: &ash::Device, completed_frame: u64) {
+        self.retired.retain(|&(frame, pipeline)| {
+            if frame <= completed_frame {
+                unsafe { device.destroy_pipeline(pipeline, None) };
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Destroy the pipeline, any retired pipelines and the shader modules. The device must be
+    /// idle.
+    pub fn cleanup(&mut self, device: &ash::Device) {
+        unsafe {
+            for (_, pipeline) in self.retired.drain(..) {
+                device.destroy_pipeline(pipeline, None);
+            }
+
+            if self.pipeline != vk::Pipeline::null() {
+                device.destroy_pipeline(self.pipeline, None);
+                self.pipeline = vk::Pipeline::null();
+            }
+        }
+
+        for watched in self.stages.drain(..) {
+            watched.stage.cleanup(device);
+        }
+    }
+
+    fn create_pipeline(
+        &self,
+        descriptor: &GraphicsPipelineDescriptor,
+        device: &ash::Device,
+    ) -> anyhow::Result<vk::Pipeline> {
+        let stages: Vec<vk::PipelineShaderStageCreateInfo> = self
+            .stages
+            .iter()
+            .flat_map(|watched| watched.stage.create_infos())
+            .map(|create_info| *create_info)
+            .collect();
+
+        let baked = descriptor.as_baked();
+
+        let create_info =
+            baked.as_pipeline_create_info(&stages, self.layout, self.render_pass, self.subpass);
+
+        let pipelines = unsafe {
+            device.create_graphics_pipelines(vk::PipelineCache::null(), &[*create_info], None)
+        };
+
+        match pipelines {
+            Ok(pipelines) => Ok(pipelines[0]),
+            Err((_, result)) => Err(result.into()),
+        }
+    }
+}
+
+fn load_stage(
+    watcher: &ShaderWatcher,
+    id: WatchedShaderId,
+    bytes: &[u8],
+    entry_points: &[(vk::ShaderStageFlags, &str)],
+    api_version: Option<u32>,
+    device: &ash::Device,
+    debug_utils_loader: Option<&DebugUtilsLoader>,
+) -> anyhow::Result<ShaderStage> {
+    let path = watcher.path(id)?;
+
+    ShaderStage::load(
+        bytes,
+        &path.display().to_string(),
+        entry_points,
+        device,
+        debug_utils_loader,
+        api_version,
+    )
+    .map_err(|error| anyhow::anyhow!("Failed to load {:?}: {}", path, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn pipelines_are_send_and_sync() {
+        assert_send_sync::<ReloadablePipeline>();
+    }
+
+    /// A unique, empty directory that's removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "ash-opinionated-abstractions-{}-{}",
+                name,
+                std::process::id()
+            ));
+
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Write a file with an explicit modification time, so that the test doesn't depend on the
+    /// resolution of the file system's timestamps.
+    fn write(path: &Path, bytes: &[u8], seconds: u64) {
+        let file = std::fs::File::create(path).unwrap();
+        std::io::Write::write_all(&mut &file, bytes).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    fn spirv(bound: u32) -> Vec<u8> {
+        [0x0723_0203, 0x0001_0000, 0, bound, 0]
+            .iter()
+            .flat_map(|word: &u32| word.to_le_bytes())
+            .collect()
+    }
+
+    fn changed(watcher: &mut ShaderWatcher) -> Vec<(WatchedShaderId, Vec<u8>)> {
+        watcher
+            .poll()
+            .into_iter()
+            .map(|change| (change.id, change.bytes))
+            .collect()
+    }
+
+    #[test]
+    fn watching_a_path_twice_returns_the_same_id() {
+        let dir = TempDir::new("watch-twice");
+        let a = dir.0.join("a.spv");
+        let b = dir.0.join("b.spv");
+        write(&a, &spirv(1), 1_000);
+
+        let mut watcher = ShaderWatcher::new();
+        let id = watcher.watch(&a);
+
+        assert_eq!(watcher.watch(&a), id);
+        assert_ne!(watcher.watch(&b), id);
+        assert_eq!(watcher.path(id).unwrap(), a);
+        assert_eq!(watcher.read(id).unwrap(), spirv(1));
+    }
+
+    #[test]
+    fn ids_from_other_watchers_are_rejected() {
+        let mut watcher = ShaderWatcher::new();
+        let mut other = ShaderWatcher::new();
+        let id = other.watch("a.spv");
+
+        watcher.watch("a.spv");
+
+        assert!(watcher.path(id).is_err());
+        assert!(watcher.read(id).is_err());
+    }
+
+    #[test]
+    fn reports_changes_once_and_retries_invalid_files() {
+        let dir = TempDir::new("poll");
+        let path = dir.0.join("shader.spv");
+        write(&path, &spirv(1), 1_000);
+
+        let mut watcher = ShaderWatcher::new();
+        let id = watcher.watch(&path);
+
+        assert!(changed(&mut watcher).is_empty());
+
+        // A partially written file isn't a whole number of words.
+        write(&path, &spirv(2)[..7], 2_000);
+        assert!(changed(&mut watcher).is_empty());
+
+        // Once it's complete, it's reported even though the modification time is the same as
+        // the partial write.
+        write(&path, &spirv(2), 2_000);
+        assert_eq!(changed(&mut watcher), [(id, spirv(2))]);
+        assert!(changed(&mut watcher).is_empty());
+
+        // A file that isn't SPIR-V at all is also skipped.
+        write(&path, b"twenty bytes of text", 3_000);
+        assert!(changed(&mut watcher).is_empty());
+
+        write(&path, &spirv(3), 4_000);
+        assert_eq!(changed(&mut watcher), [(id, spirv(3))]);
+        assert!(changed(&mut watcher).is_empty());
+
+        // A file that's deleted is skipped until it comes back.
+        std::fs::remove_file(&path).unwrap();
+        assert!(changed(&mut watcher).is_empty());
+
+        write(&path, &spirv(4), 5_000);
+        assert_eq!(changed(&mut watcher), [(id, spirv(4))]);
+    }
+}
@@ -0,0 +1,341 @@
+use crate::{cstr_from_array, vulkan_debug_utils_callback, CStrList, DebugMessengerSettings};
+use ash::extensions::ext::DebugUtils as DebugUtilsLoader;
+use ash::extensions::khr::GetPhysicalDeviceProperties2;
+use ash::vk;
+use std::ffi::{CStr, CString};
+
+const VALIDATION_LAYER: &[u8] = b"VK_LAYER_KHRONOS_validation\0";
+const PORTABILITY_ENUMERATION_EXTENSION: &[u8] = b"VK_KHR_portability_enumeration\0";
+// `VK_INSTANCE_CREATE_ENUMERATE_PORTABILITY_BIT_KHR`, which is newer than our ash version.
+const ENUMERATE_PORTABILITY_FLAG: vk::InstanceCreateFlags = vk::InstanceCreateFlags::from_raw(1);
+
+pub(crate) fn cstr(bytes: &'static [u8]) -> &'static CStr {
+    CStr::from_bytes_with_nul(bytes).unwrap()
+}
+
+/// A builder for an [`InstanceBundle`] that checks layer and extension availability and
+/// optionally sets up validation and a debug messenger.
+pub struct InstanceBuilder<'a> {
+    app_name: &'a str,
+    app_version: u32,
+    api_version: u32,
+    required_layers: Vec<&'a CStr>,
+    optional_layers: Vec<&'a CStr>,
+    required_extensions: Vec<&'a CStr>,
+    optional_extensions: Vec<&'a CStr>,
+    validation: bool,
+    debug_messenger: bool,
+    debug_messenger_settings: DebugMessengerSettings,
+    portability: bool,
+}
+
+impl<'a> InstanceBuilder<'a> {
+    pub fn new(app_name: &'a str) -> Self {
+        Self {
+            app_name,
+            app_version: 0,
+            api_version: vk::API_VERSION_1_0,
+            required_layers: Vec::new(),
+            optional_layers: Vec::new(),
+            required_extensions: Vec::new(),
+            optional_extensions: Vec::new(),
+            validation: false,
+            debug_messenger: false,
+            debug_messenger_settings: DebugMessengerSettings::default(),
+            portability: true,
+        }
+    }
+
+    pub fn app_version(mut self, app_version: u32) -> Self {
+        self.app_version = app_version;
+        self
+    }
+
+    pub fn api_version(mut self, api_version: u32) -> Self {
+        self.api_version = api_version;
+        self
+    }
+
+    pub fn required_layers(mut self, layers: &[&'a CStr]) -> Self {
+        self.required_layers.extend_from_slice(layers);
+        self
+    }
+
+    /// Layers that are enabled only if they're available.
+    pub fn optional_layers(mut self, layers: &[&'a CStr]) -> Self {
+        self.optional_layers.extend_from_slice(layers);
+        self
+    }
+
+    pub fn required_extensions(mut self, extensions: &[&'a CStr]) -> Self {
+        self.required_extensions.extend_from_slice(extensions);
+        self
+    }
+
+    /// Extensions that are enabled only if they're available.
+    pub fn optional_extensions(mut self, extensions: &[&'a CStr]) -> Self {
+        self.optional_extensions.extend_from_slice(extensions);
+        self
+    }
+
+    /// Enable `VK_LAYER_KHRONOS_validation` if it's available.
+    pub fn validation(mut self, validation: bool) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Create a debug messenger that uses [`vulkan_debug_utils_callback`], if `VK_EXT_debug_utils`
+    /// is available.
+    pub fn debug_messenger(mut self, debug_messenger: bool) -> Self {
+        self.debug_messenger = debug_messenger;
+        self
+    }
+
+    /// Muting, severity overrides and message capture for the debug messenger. These are kept
+    /// alive by the [`InstanceBundle`], and validation errors can be checked for afterwards with
+    /// [`DebugMessengerSettings::check_no_validation_errors`] on
+    /// [`InstanceBundle::debug_messenger_settings`].
+    pub fn debug_messenger_settings(mut self, settings: DebugMessengerSettings) -> Self {
+        self.debug_messenger_settings = settings;
+        self
+    }
+
+    /// Enable `VK_KHR_portability_enumeration` if it's available, so that portability
+    /// implementations such as MoltenVK are enumerated. Defaults to `true`.
+    ///
+    /// [`DeviceBuilder`](crate::DeviceBuilder) enables `VK_KHR_portability_subset` on devices
+    /// that support it, which needs `VK_KHR_get_physical_device_properties2` on Vulkan 1.0
+    /// instances, so that's enabled too if it's available.
+    pub fn portability(mut self, portability: bool) -> Self {
+        self.portability = portability;
+        self
+    }
+
+    pub fn build(self, entry: ash::Entry) -> anyhow::Result<InstanceBundle> {
+        let available_layers = entry.enumerate_instance_layer_properties()?;
+        let available_extensions = entry.enumerate_instance_extension_properties()?;
+
+        let mut optional_layers = self.optional_layers;
+
+        if self.validation {
+            optional_layers.push(cstr(VALIDATION_LAYER));
+        }
+
+        let mut optional_extensions = self.optional_extensions;
+
+        if self.debug_messenger {
+            optional_extensions.push(DebugUtilsLoader::name());
+        }
+
+        if self.portability {
+            optional_extensions.push(cstr(PORTABILITY_ENUMERATION_EXTENSION));
+
+            if self.api_version < vk::API_VERSION_1_1 {
+                optional_extensions.push(GetPhysicalDeviceProperties2::name());
+            }
+        }
+
+        let enabled_layers = select_names(
+            "layer",
+            &self.required_layers,
+            &optional_layers,
+            available_layers
+                .iter()
+                .map(|layer| unsafe { cstr_from_array(&layer.layer_name) }),
+        )?;
+
+        let enabled_extensions = select_names(
+            "extension",
+            &self.required_extensions,
+            &optional_extensions,
+            available_extensions
+                .iter()
+                .map(|extension| unsafe { cstr_from_array(&extension.extension_name) }),
+        )?;
+
+        let has_debug_utils = enabled_extensions.contains(&DebugUtilsLoader::name());
+        let create_debug_messenger = self.debug_messenger && has_debug_utils;
+
+        let mut flags = vk::InstanceCreateFlags::empty();
+
+        if enabled_extensions.contains(&cstr(PORTABILITY_ENUMERATION_EXTENSION)) {
+            flags |= ENUMERATE_PORTABILITY_FLAG;
+        }
+
+        let app_name = CString::new(self.app_name)?;
+        let enabled_layers = CStrList::new(enabled_layers);
+        let enabled_extensions = CStrList::new(enabled_extensions);
+
+        let app_info = vk::ApplicationInfo::builder()
+            .application_name(&app_name)
+            .application_version(self.app_version)
+            .engine_name(&app_name)
+            .api_version(self.api_version);
+
+        // Boxed so that the pointer given to the callback stays valid when the bundle moves.
+        let debug_messenger_settings = Box::new(self.debug_messenger_settings);
+        let mut debug_messenger_info = debug_messenger_create_info(&debug_messenger_settings);
+
+        let mut instance_info = vk::InstanceCreateInfo::builder()
+            .flags(flags)
+            .application_info(&app_info)
+            .enabled_layer_names(enabled_layers.pointers())
+            .enabled_extension_names(enabled_extensions.pointers());
+
+        // Chaining the messenger info means that instance creation and destruction are covered.
+        if create_debug_messenger {
+            instance_info = instance_info.push_next(&mut debug_messenger_info);
+        }
+
+        let instance = unsafe { entry.create_instance(&instance_info, None) }?;
+
+        let debug_utils_loader = if has_debug_utils {
+            Some(DebugUtilsLoader::new(&entry, &instance))
+        } else {
+            None
+        };
+
+        let debug_messenger = match &debug_utils_loader {
+            Some(debug_utils_loader) if create_debug_messenger => {
+                match unsafe {
+                    debug_utils_loader.create_debug_utils_messenger(
+                        &debug_messenger_create_info(&debug_messenger_settings),
+                        None,
+                    )
+                } {
+                    Ok(debug_messenger) => debug_messenger,
+                    Err(error) => {
+                        unsafe { instance.destroy_instance(None) };
+                        return Err(error.into());
+                    }
+                }
+            }
+            _ => vk::DebugUtilsMessengerEXT::null(),
+        };
+
+        Ok(InstanceBundle {
+            enabled_layers: enabled_layers
+                .list
+                .iter()
+                .map(|&name| name.into())
+                .collect(),
+            enabled_extensions: enabled_extensions
+                .list
+                .iter()
+                .map(|&name| name.into())
+                .collect(),
+            debug_messenger,
+            debug_messenger_settings,
+            debug_utils_loader,
+            instance,
+            api_version: self.api_version,
+            entry,
+        })
+    }
+}
+
+fn debug_messenger_create_info(
+    settings: &DebugMessengerSettings,
+) -> vk::DebugUtilsMessengerCreateInfoEXTBuilder<'_> {
+    vk::DebugUtilsMessengerCreateInfoEXT::builder()
+        .message_severity(
+            vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
+                | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+                | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+        )
+        .message_type(
+            vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+        )
+        .pfn_user_callback(Some(vulkan_debug_utils_callback))
+        .user_data(settings as *const DebugMessengerSettings as *mut std::ffi::c_void)
+}
+
+/// Combine required and optional names, failing if any required names aren't available and
+/// logging the optional names that aren't.
+fn select_names<'a, 'b>(
+    kind: &str,
+    required: &[&'a CStr],
+    optional: &[&'a CStr],
+    available: impl Iterator<Item = &'b CStr> + Clone,
+) -> anyhow::Result<Vec<&'a CStr>> {
+    let is_available = |name: &CStr| available.clone().any(|available| available == name);
+
+    let missing: Vec<_> = required
+        .iter()
+        .filter(|name| !is_available(name))
+        .map(|name| name.to_string_lossy())
+        .collect();
+
+    if !missing.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Required instance {}s are not available: {}",
+            kind,
+            missing.join(", ")
+        ));
+    }
+
+    let mut enabled = required.to_vec();
+
+    for &name in optional {
+        if is_available(name) {
+            if !enabled.contains(&name) {
+                enabled.push(name);
+            }
+        } else {
+            log::warn!("Optional instance {} {:?} is not available", kind, name);
+        }
+    }
+
+    Ok(enabled)
+}
+
+/// An instance along with everything that was created with it. Destroys the debug messenger and
+/// then the instance when dropped.
+pub struct InstanceBundle {
+    pub entry: ash::Entry,
+    pub instance: ash::Instance,
+    /// The `api_version` that the instance was created with.
+    pub api_version: u32,
+    pub debug_utils_loader: Option<DebugUtilsLoader>,
+    /// Null if a debug messenger wasn't requested or `VK_EXT_debug_utils` wasn't available.
+    pub debug_messenger: vk::DebugUtilsMessengerEXT,
+    pub enabled_layers: Vec<CString>,
+    pub enabled_extensions: Vec<CString>,
+    /// Read by the debug messenger, so it has to outlive it.
+    debug_messenger_settings: Box<DebugMessengerSettings>,
+}
+
+impl InstanceBundle {
+    pub fn has_layer(&self, name: &CStr) -> bool {
+        self.enabled_layers
+            .iter()
+            .any(|layer| layer.as_c_str() == name)
+    }
+
+    pub fn has_extension(&self, name: &CStr) -> bool {
+        self.enabled_extensions
+            .iter()
+            .any(|extension| extension.as_c_str() == name)
+    }
+
+    pub fn debug_messenger_settings(&self) -> &DebugMessengerSettings {
+        &self.debug_messenger_settings
+    }
+}
+
+impl Drop for InstanceBundle {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(debug_utils_loader) = &self.debug_utils_loader {
+                if self.debug_messenger != vk::DebugUtilsMessengerEXT::null() {
+                    debug_utils_loader.destroy_debug_utils_messenger(self.debug_messenger, None);
+                }
+            }
+
+            self.instance.destroy_instance(None);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use ash::extensions::ext::DebugUtils as DebugUtilsLoader;
+use ash::vk;
+use std::ffi::CString;
+
+/// Labels are only for debugging, so rather than failing, names are cut off at the first nul
+/// byte.
+fn label_name(name: &str) -> CString {
+    let name = name.split('\0').next().unwrap_or_default();
+    CString::new(name).unwrap()
+}
+
+/// A colour of `None` leaves the label uncoloured, which Vulkan represents as all zeroes.
+fn with_label<R>(
+    name: &str,
+    color: Option<[f32; 4]>,
+    f: impl FnOnce(&vk::DebugUtilsLabelEXT) -> R,
+) -> R {
+    let name = label_name(name);
+    let label = vk::DebugUtilsLabelEXT::builder()
+        .label_name(&name)
+        .color(color.unwrap_or_default());
+
+    f(&label)
+}
+
+/// Insert a single label into a command buffer. Does nothing if `debug_utils_loader` is `None`.
+pub fn insert_command_buffer_label(
+    debug_utils_loader: Option<&DebugUtilsLoader>,
+    command_buffer: vk::CommandBuffer,
+    name: &str,
+    color: Option<[f32; 4]>,
+) {
+    if let Some(debug_utils_loader) = debug_utils_loader {
+        with_label(name, color, |label| unsafe {
+            debug_utils_loader.cmd_insert_debug_utils_label(command_buffer, label)
+        });
+    }
+}
+
+/// Insert a single label into a queue. Does nothing if `debug_utils_loader` is `None`.
+pub fn insert_queue_label(
+    debug_utils_loader: Option<&DebugUtilsLoader>,
+    queue: vk::Queue,
+    name: &str,
+    color: Option<[f32; 4]>,
+) {
+    if let Some(debug_utils_loader) = debug_utils_loader {
+        with_label(name, color, |label| unsafe {
+            debug_utils_loader.queue_insert_debug_utils_label(queue, label)
+        });
+    }
+}
+
+/// A labelled region of a command buffer, which is ended when this is dropped. Nested regions
+/// borrow the enclosing one, so they are always ended first. Does nothing if
+/// `debug_utils_loader` is `None`.
+#[must_use = "the label is ended as soon as this is dropped"]
+pub struct CommandBufferLabel<'a> {
+    debug_utils_loader: Option<&'a DebugUtilsLoader>,
+    command_buffer: vk::CommandBuffer,
+}
+
+impl<'a> CommandBufferLabel<'a> {
+    pub fn begin(
+        debug_utils_loader: Option<&'a DebugUtilsLoader>,
+        command_buffer: vk::CommandBuffer,
+        name: &str,
+        color: Option<[f32; 4]>,
+    ) -> Self {
+        if let Some(debug_utils_loader) = debug_utils_loader {
+            with_label(name, color, |label| unsafe {
+                debug_utils_loader.cmd_begin_debug_utils_label(command_buffer, label)
+            });
+        }
+
+        Self {
+            debug_utils_loader,
+            command_buffer,
+        }
+    }
+
+    /// Begin a region inside this one.
+    pub fn nested(&self, name: &str, color: Option<[f32; 4]>) -> CommandBufferLabel<'_> {
+        CommandBufferLabel::begin(self.debug_utils_loader, self.command_buffer, name, color)
+    }
+
+    /// Insert a single label inside this region.
+    pub fn insert(&self, name: &str, color: Option<[f32; 4]>) {
+        insert_command_buffer_label(self.debug_utils_loader, self.command_buffer, name, color);
+    }
+
+    pub fn command_buffer(&self) -> vk::CommandBuffer {
+        self.command_buffer
+    }
+
+    /// End the region now rather than at the end of the scope.
+    pub fn end(self) {}
+}
+
+impl Drop for CommandBufferLabel<'_> {
+    fn drop(&mut self) {
+        if let Some(debug_utils_loader) = self.debug_utils_loader {
+            unsafe { debug_utils_loader.cmd_end_debug_utils_label(self.command_buffer) };
+        }
+    }
+}
+
+/// A labelled region of a queue's submissions, which is ended when this is dropped. Does nothing
+/// if `debug_utils_loader` is `None`.
+#[must_use = "the label is ended as soon as this is dropped"]
+pub struct QueueLabel<'a> {
+    debug_utils_loader: Option<&'a DebugUtilsLoader>,
+    queue: vk::Queue,
+}
+
+impl<'a> QueueLabel<'a> {
+    pub fn begin(
+        debug_utils_loader: Option<&'a DebugUtilsLoader>,
+        queue: vk::Queue,
+        name: &str,
+        color: Option<[f32; 4]>,
+    ) -> Self {
+        if let Some(debug_utils_loader) = debug_utils_loader {
+            with_label(name, color, |label| unsafe {
+                debug_utils_loader.queue_begin_debug_utils_label(queue, label)
+            });
+        }
+
+        Self {
+            debug_utils_loader,
+            queue,
+        }
+    }
+
+    /// Begin a region inside this one.
+    pub fn nested(&self, name: &str, color: Option<[f32; 4]>) -> QueueLabel<'_> {
+        QueueLabel::begin(self.debug_utils_loader, self.queue, name, color)
+    }
+
+    /// Insert a single label inside this region.
+    pub fn insert(&self, name: &str, color: Option<[f32; 4]>) {
+        insert_queue_label(self.debug_utils_loader, self.queue, name, color);
+    }
+
+    pub fn queue(&self) -> vk::Queue {
+        self.queue
+    }
+
+    /// End the region now rather than at the end of the scope.
+    pub fn end(self) {}
+}
+
+impl Drop for QueueLabel<'_> {
+    fn drop(&mut self) {
+        if let Some(debug_utils_loader) = self.debug_utils_loader {
+            unsafe { debug_utils_loader.queue_end_debug_utils_label(self.queue) };
+        }
+    }
+}
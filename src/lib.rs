@@ -5,6 +5,67 @@ use gpu_allocator::vulkan::{Allocation, AllocationCreateDesc, Allocator};
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 
+#[cfg(feature = "shader-compilation")]
+mod compile;
+mod debug;
+mod device;
+mod hot_reload;
+mod instance;
+mod label;
+mod queue_families;
+mod reflection;
+mod report;
+mod requirements;
+mod scoring;
+mod shader;
+mod specialization;
+mod spirv;
+mod surface;
+mod vertex;
+mod vertex_input;
+
+#[cfg(feature = "shader-compilation")]
+pub use compile::{
+    compile_shader_file, compile_shader_source, load_shader_module_from_source,
+    ShaderCompileDescriptor, ShaderSourceLanguage,
+};
+pub use debug::{
+    vulkan_debug_utils_callback, DebugMessage, DebugMessageCollector, DebugMessengerSettings,
+    DebugObject, SeverityOverride,
+};
+pub use device::{DeviceBuilder, DeviceBundle, Queues};
+pub use hot_reload::{
+    ReloadablePipeline, ShaderChange, ShaderWatcher, WatchedShaderDescriptor, WatchedShaderId,
+};
+pub use instance::{InstanceBuilder, InstanceBundle};
+pub use label::{insert_command_buffer_label, insert_queue_label, CommandBufferLabel, QueueLabel};
+pub use queue_families::{QueueFamilies, QueueFamily};
+pub use reflection::{
+    DescriptorBinding, EntryPoint, NumericType, PushConstantBlock, ReflectedPipelineLayout,
+    ShaderInput, ShaderReflection,
+};
+pub use report::{DeviceReport, DeviceSelectionReport, RejectionReason};
+pub use requirements::{DeviceRequirements, LimitRequirements};
+pub use scoring::{
+    default_device_score, DeviceOverride, DeviceScoringInfo, DEVICE_OVERRIDE_ENV_VAR,
+};
+pub use shader::ShaderStage;
+pub use specialization::{
+    load_shader_module_as_specialized_stage, SpecializationConstant, SpecializationConstants,
+};
+pub use spirv::{load_shader_module_checked, max_spirv_version, read_spirv, SpirvModuleInfo};
+pub use surface::{
+    negotiate_swapchain_create_info, PresentModePreference, SurfaceFormatSelection,
+    SwapchainDescriptor, BGRA8_SRGB, HDR10_ST2084, SCRGB_LINEAR,
+};
+pub use vertex::{
+    create_vertex_input_layout, Vertex, VertexBindingLayout, VertexFormat, VertexInputLayout,
+};
+pub use vertex_input::validate_vertex_input;
+
+#[cfg(feature = "derive")]
+pub use ash_opinionated_abstractions_derive::Vertex;
+
 /// A list of C strings and their associated pointers
 pub struct CStrList<'a> {
     list: Vec<&'a CStr>,
@@ -23,13 +84,217 @@ impl<'a> CStrList<'a> {
     }
 }
 
+/// An owned list of C strings and their associated pointers, for lists that are assembled at
+/// runtime. Duplicates are ignored.
+#[derive(Default, Debug)]
+pub struct OwnedCStrList {
+    list: Vec<CString>,
+    pointers: Vec<*const c_char>,
+}
+
+impl OwnedCStrList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_strs<S: AsRef<str>>(strs: impl IntoIterator<Item = S>) -> anyhow::Result<Self> {
+        let mut list = Self::new();
+
+        for string in strs {
+            list.push_str(string.as_ref())?;
+        }
+
+        Ok(list)
+    }
+
+    /// Parse a comma-separated list from an environment variable, ignoring whitespace and empty
+    /// entries. An unset variable gives an empty list.
+    pub fn from_env(var: &str) -> anyhow::Result<Self> {
+        match std::env::var(var) {
+            Ok(value) => Self::from_strs(
+                value
+                    .split(',')
+                    .map(|entry| entry.trim())
+                    .filter(|entry| !entry.is_empty()),
+            ),
+            Err(std::env::VarError::NotPresent) => Ok(Self::new()),
+            Err(error) => Err(anyhow::anyhow!("Failed to read {}: {}", var, error)),
+        }
+    }
+
+    /// Add a string to the list. Returns `false` if it was already present.
+    pub fn push_str(&mut self, string: &str) -> anyhow::Result<bool> {
+        Ok(self.push(CString::new(string)?))
+    }
+
+    /// Add a C string to the list. Returns `false` if it was already present.
+    pub fn push_cstr(&mut self, cstr: &CStr) -> bool {
+        self.push(cstr.to_owned())
+    }
+
+    fn push(&mut self, string: CString) -> bool {
+        if self.contains(&string) {
+            return false;
+        }
+
+        // The pointer stays valid when `list` reallocates, as it points into the `CString`'s own
+        // heap allocation.
+        self.pointers.push(string.as_ptr());
+        self.list.push(string);
+        true
+    }
+
+    pub fn extend_from_cstrs<'a>(&mut self, cstrs: impl IntoIterator<Item = &'a CStr>) {
+        for cstr in cstrs {
+            self.push_cstr(cstr);
+        }
+    }
+
+    pub fn merge(&mut self, other: &OwnedCStrList) {
+        self.extend_from_cstrs(other.iter());
+    }
+
+    pub fn contains(&self, cstr: &CStr) -> bool {
+        self.list.iter().any(|string| string.as_c_str() == cstr)
+    }
+
+    pub fn contains_str(&self, string: &str) -> bool {
+        self.list
+            .iter()
+            .any(|cstring| cstring.as_bytes() == string.as_bytes())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CStr> + Clone {
+        self.list.iter().map(|string| string.as_c_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Borrow the list as a [`CStrList`], for the functions that take one.
+    pub fn as_cstr_list(&self) -> CStrList<'_> {
+        CStrList::new(self.iter().collect())
+    }
+
+    pub fn pointers(&self) -> &[*const c_char] {
+        &self.pointers
+    }
+}
+
+impl Clone for OwnedCStrList {
+    fn clone(&self) -> Self {
+        let mut list = Self::new();
+        list.merge(self);
+        list
+    }
+}
+
+impl<'a> From<&CStrList<'a>> for OwnedCStrList {
+    fn from(list: &CStrList<'a>) -> Self {
+        let mut owned = Self::new();
+        owned.extend_from_cstrs(list.list.iter().copied());
+        owned
+    }
+}
+
+/// Everything that physical device selection needs except for the surface.
+pub struct DeviceSelectionDescriptor<'a> {
+    pub required_extensions: &'a CStrList<'a>,
+    /// Extensions that don't affect eligibility but add to the score of devices that support
+    /// them. The supported ones are returned in
+    /// [`SelectedPhysicalDevice::enabled_optional_extensions`].
+    pub optional_extensions: &'a CStrList<'a>,
+    pub requirements: &'a DeviceRequirements,
+    /// The `api_version` that the instance was created with, such as
+    /// [`InstanceBundle::api_version`]. [`DeviceOverride::Uuid`] needs at least Vulkan 1.1, and
+    /// requesting Vulkan 1.1 or 1.2 features in [`DeviceRequirements`] needs at least 1.2.
+    pub instance_api_version: u32,
+    /// Used to rank eligible devices. [`default_device_score`] is used if this is `None`.
+    pub scorer: Option<&'a dyn Fn(&DeviceScoringInfo) -> u64>,
+    /// Only select devices that match this. If `None`, the [`DEVICE_OVERRIDE_ENV_VAR`]
+    /// environment variable is checked instead.
+    pub device_override: Option<DeviceOverride>,
+}
+
+/// Select the best physical device that can present to `surface`.
+///
+/// The surface format is picked from `format_preferences` in order, falling back to the first
+/// format that the surface supports.
+///
+/// Vulkan errors from querying an individual device don't fail the selection. Instead, the
+/// device is rejected with a [`RejectionReason::VulkanError`] in the report.
 pub fn select_physical_device(
     instance: &ash::Instance,
-    required_extensions: &CStrList,
+    descriptor: &DeviceSelectionDescriptor,
     surface_loader: &SurfaceLoader,
     surface: vk::SurfaceKHR,
-    desired_format: vk::Format,
-) -> anyhow::Result<Option<(vk::PhysicalDevice, u32, vk::SurfaceFormatKHR)>> {
+    format_preferences: &[vk::SurfaceFormatKHR],
+) -> anyhow::Result<(
+    Option<(SelectedPhysicalDevice, SurfaceFormatSelection)>,
+    DeviceSelectionReport,
+)> {
+    let (selection, report) = select_physical_device_inner(
+        instance,
+        descriptor,
+        Some(&SurfaceInfo {
+            loader: surface_loader,
+            surface,
+            format_preferences,
+        }),
+    )?;
+
+    let selection = selection.map(|(selected, surface_format)| {
+        (
+            selected,
+            surface_format.expect("A surface format is always selected when a surface is given"),
+        )
+    });
+
+    Ok((selection, report))
+}
+
+/// Select a physical device without a surface, for compute-only or offline use.
+///
+/// This performs the same checks as [`select_physical_device`], minus the present support and
+/// surface format checks. Devices without a graphics queue family are accepted if they have a
+/// compute family, which is then used as [`QueueFamilies::graphics`].
+pub fn select_physical_device_headless(
+    instance: &ash::Instance,
+    descriptor: &DeviceSelectionDescriptor,
+) -> anyhow::Result<(Option<SelectedPhysicalDevice>, DeviceSelectionReport)> {
+    let (selection, report) = select_physical_device_inner(instance, descriptor, None)?;
+
+    Ok((selection.map(|(selected, _)| selected), report))
+}
+
+/// A physical device that passed every check during selection.
+#[derive(Clone)]
+pub struct SelectedPhysicalDevice {
+    pub physical_device: vk::PhysicalDevice,
+    pub properties: vk::PhysicalDeviceProperties,
+    pub queue_families: QueueFamilies,
+    /// The optional extensions that the device supports.
+    pub enabled_optional_extensions: OwnedCStrList,
+}
+
+struct SurfaceInfo<'a> {
+    loader: &'a SurfaceLoader,
+    surface: vk::SurfaceKHR,
+    format_preferences: &'a [vk::SurfaceFormatKHR],
+}
+
+type Selection = (SelectedPhysicalDevice, Option<SurfaceFormatSelection>);
+
+fn select_physical_device_inner(
+    instance: &ash::Instance,
+    descriptor: &DeviceSelectionDescriptor,
+    surface_info: Option<&SurfaceInfo>,
+) -> anyhow::Result<(Option<Selection>, DeviceSelectionReport)> {
     let physical_devices = unsafe { instance.enumerate_physical_devices() }?;
 
     log::info!(
@@ -38,76 +303,234 @@ pub fn select_physical_device(
         if physical_devices.len() == 1 { "" } else { "s" }
     );
 
-    let selection = physical_devices
-        .into_iter()
-        .filter_map(|physical_device| unsafe {
-            let properties = instance.get_physical_device_properties(physical_device);
+    let device_override = descriptor
+        .device_override
+        .clone()
+        .or_else(DeviceOverride::from_env);
 
-            log::info!("");
-            log::info!(
-                "Checking Device: {:?}",
-                cstr_from_array(&properties.device_name)
-            );
+    if let Some(device_override) = &device_override {
+        log::info!("Using device override: {:?}", device_override);
+    }
 
-            log::debug!("Api version: {}", properties.api_version);
-
-            let queue_family = instance
-                .get_physical_device_queue_family_properties(physical_device)
-                .into_iter()
-                .enumerate()
-                .position(|(i, queue_family_properties)| {
-                    queue_family_properties
-                        .queue_flags
-                        .contains(vk::QueueFlags::GRAPHICS)
-                        && surface_loader
-                            .get_physical_device_surface_support(physical_device, i as u32, surface)
-                            .unwrap()
-                })
-                .map(|queue_family| queue_family as u32);
+    let mut report = DeviceSelectionReport::default();
+    let mut selection: Option<(u64, Selection)> = None;
+
+    for (index, physical_device) in physical_devices.into_iter().enumerate() {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+
+        let mut device_report = DeviceReport::new(index, &properties);
+
+        log::info!("");
+        log::info!("Checking Device: {:?}", device_report.name);
+
+        log::debug!("Api version: {}", properties.api_version);
+
+        if let Some(device_override) = &device_override {
+            let matches_override = device_override.matches(index, &device_report.name, || {
+                device_uuid(
+                    instance,
+                    descriptor.instance_api_version,
+                    physical_device,
+                    &properties,
+                )
+            });
 
             log::info!(
-                "  Checking for a graphics queue family: {}",
-                tick(queue_family.is_some())
+                "  Checking against the device override: {}",
+                tick(matches_override)
             );
 
-            let queue_family = match queue_family {
-                Some(queue_family) => queue_family,
-                None => return None,
+            if !matches_override {
+                device_report
+                    .rejections
+                    .push(RejectionReason::ExcludedByOverride);
+            }
+        }
+
+        let mut enabled_optional_extensions = OwnedCStrList::new();
+
+        let candidate = unsafe {
+            check_physical_device(
+                instance,
+                physical_device,
+                &properties,
+                descriptor,
+                surface_info,
+                &mut device_report.rejections,
+                &mut enabled_optional_extensions,
+            )
+        };
+
+        device_report.supported_optional_extensions = enabled_optional_extensions
+            .iter()
+            .map(|extension| extension.to_string_lossy().into_owned())
+            .collect();
+
+        if let Some((queue_families, surface_format)) = candidate {
+            let memory_properties =
+                unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+            let scoring_info = DeviceScoringInfo {
+                physical_device,
+                properties: &properties,
+                memory_properties: &memory_properties,
+                queue_families: &queue_families,
+                enabled_optional_extensions: &enabled_optional_extensions,
             };
 
-            let surface_formats = surface_loader
-                .get_physical_device_surface_formats(physical_device, surface)
-                .unwrap();
+            let score = match descriptor.scorer {
+                Some(scorer) => scorer(&scoring_info),
+                None => default_device_score(&scoring_info),
+            };
 
-            let surface_format = surface_formats
-                .iter()
-                .find(|surface_format| {
-                    surface_format.format == desired_format
-                        && surface_format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
-                })
-                .or_else(|| surface_formats.get(0));
+            log::debug!("  Score: {}", score);
 
-            log::info!(
-                "  Checking for an appropriate surface format: {}",
-                tick(surface_format.is_some())
-            );
+            device_report.score = Some(score);
 
-            let surface_format = match surface_format {
-                Some(surface_format) => *surface_format,
-                None => return None,
+            let is_best = match &selection {
+                Some((best_score, _)) => score >= *best_score,
+                None => true,
             };
 
-            log::info!("  Checking for required extensions:");
+            if is_best {
+                let selected = SelectedPhysicalDevice {
+                    physical_device,
+                    properties,
+                    queue_families,
+                    enabled_optional_extensions,
+                };
+
+                selection = Some((score, (selected, surface_format)));
+                report.selected = Some(report.devices.len());
+            }
+        }
+
+        report.devices.push(device_report);
+    }
+
+    log::info!("");
+
+    if let Some(selected) = report.selected_device() {
+        log::info!("Using device {:?}", selected.name);
+    }
+
+    Ok((selection.map(|(_, selection)| selection), report))
+}
 
-            let supported_device_extensions = instance
-                .enumerate_device_extension_properties(physical_device)
-                .unwrap();
+/// Run every check on a physical device, returning the selected queue families and surface
+/// format if all of them passed. The optional extensions that the device supports are written
+/// to `enabled_optional_extensions` either way.
+unsafe fn check_physical_device(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    properties: &vk::PhysicalDeviceProperties,
+    descriptor: &DeviceSelectionDescriptor,
+    surface_info: Option<&SurfaceInfo>,
+    rejections: &mut Vec<RejectionReason>,
+    enabled_optional_extensions: &mut OwnedCStrList,
+) -> Option<(QueueFamilies, Option<SurfaceFormatSelection>)> {
+    let queue_family_properties =
+        instance.get_physical_device_queue_family_properties(physical_device);
+
+    let queue_families = QueueFamilies::find(
+        &queue_family_properties,
+        |i| match surface_info {
+            Some(surface_info) => surface_info.loader.get_physical_device_surface_support(
+                physical_device,
+                i,
+                surface_info.surface,
+            ),
+            None => Ok(true),
+        },
+        surface_info.is_none(),
+    );
+
+    let queue_families = match queue_families {
+        Ok(queue_families) => {
+            if surface_info.is_some() {
+                log::info!(
+                    "  Checking for a graphics queue family: {}",
+                    tick(queue_families.is_some())
+                );
+            } else {
+                log::info!(
+                    "  Checking for a graphics or compute queue family: {}",
+                    tick(queue_families.is_some())
+                );
+            }
 
-            let mut has_required_extensions = true;
+            if queue_families.is_none() {
+                rejections.push(if surface_info.is_some() {
+                    RejectionReason::NoGraphicsQueueFamily
+                } else {
+                    RejectionReason::NoComputeQueueFamily
+                });
+            }
 
-            for required_extension in &required_extensions.list {
+            queue_families
+        }
+        Err(result) => {
+            log_vulkan_error("vkGetPhysicalDeviceSurfaceSupportKHR", result, rejections);
+            None
+        }
+    };
+
+    if let Some(queue_families) = queue_families {
+        log::info!(
+            "  Checking for an async compute queue family: {}",
+            tick(queue_families.has_async_compute())
+        );
+
+        log::info!(
+            "  Checking for a dedicated transfer queue family: {}",
+            tick(queue_families.has_dedicated_transfer())
+        );
+
+        log::debug!("  Queue families: {:?}", queue_families);
+    }
+
+    let surface_format = match surface_info {
+        Some(surface_info) => match surface_info
+            .loader
+            .get_physical_device_surface_formats(physical_device, surface_info.surface)
+        {
+            Ok(surface_formats) => {
+                let surface_format = SurfaceFormatSelection::select(
+                    &surface_formats,
+                    surface_info.format_preferences,
+                );
+
+                log::info!(
+                    "  Checking for an appropriate surface format: {}",
+                    tick(surface_format.is_some())
+                );
+
+                match surface_format {
+                    Some(surface_format) => log::debug!(
+                        "  Surface format: {:?} (preference {:?})",
+                        surface_format.surface_format,
+                        surface_format.preference_index
+                    ),
+                    None => rejections.push(RejectionReason::NoSurfaceFormat),
+                }
+
+                surface_format
+            }
+            Err(result) => {
+                log_vulkan_error("vkGetPhysicalDeviceSurfaceFormatsKHR", result, rejections);
+                None
+            }
+        },
+        None => None,
+    };
+
+    log::info!("  Checking for required extensions:");
+
+    match instance.enumerate_device_extension_properties(physical_device) {
+        Ok(supported_device_extensions) => {
+            for required_extension in &descriptor.required_extensions.list {
                 let device_has_extension = supported_device_extensions.iter().any(|extension| {
-                    &cstr_from_array(&extension.extension_name) == required_extension
+                    cstr_from_array(&extension.extension_name) == *required_extension
                 });
 
                 log::info!(
@@ -116,36 +539,89 @@ pub fn select_physical_device(
                     tick(device_has_extension)
                 );
 
-                has_required_extensions &= device_has_extension;
+                if !device_has_extension {
+                    rejections.push(RejectionReason::MissingExtension(
+                        required_extension.to_string_lossy().into_owned(),
+                    ));
+                }
             }
 
-            if !has_required_extensions {
-                return None;
+            if !descriptor.optional_extensions.list.is_empty() {
+                log::info!("  Checking for optional extensions:");
             }
 
-            Some((physical_device, queue_family, surface_format, properties))
-        })
-        .max_by_key(|(.., properties)| match properties.device_type {
-            vk::PhysicalDeviceType::DISCRETE_GPU => 2,
-            vk::PhysicalDeviceType::INTEGRATED_GPU => 1,
-            _ => 0,
-        });
-
-    log::info!("");
+            for optional_extension in &descriptor.optional_extensions.list {
+                let device_has_extension = supported_device_extensions.iter().any(|extension| {
+                    cstr_from_array(&extension.extension_name) == *optional_extension
+                });
 
-    Ok(match selection {
-        Some((physical_device, queue_family, surface_format, properties)) => {
-            unsafe {
                 log::info!(
-                    "Using device {:?}",
-                    cstr_from_array(&properties.device_name)
+                    "    * {:?}: {}",
+                    optional_extension,
+                    tick(device_has_extension)
                 );
-            }
 
-            Some((physical_device, queue_family, surface_format))
+                if device_has_extension {
+                    enabled_optional_extensions.push_cstr(optional_extension);
+                }
+            }
         }
-        None => None,
-    })
+        Err(result) => {
+            log_vulkan_error("vkEnumerateDeviceExtensionProperties", result, rejections);
+        }
+    }
+
+    descriptor.requirements.check(
+        instance,
+        descriptor.instance_api_version,
+        physical_device,
+        properties,
+        rejections,
+    );
+
+    if !rejections.is_empty() {
+        return None;
+    }
+
+    queue_families.map(|queue_families| (queue_families, surface_format))
+}
+
+fn log_vulkan_error(call: &str, result: vk::Result, rejections: &mut Vec<RejectionReason>) {
+    log::warn!("  {} failed: {}", call, result);
+
+    rejections.push(RejectionReason::VulkanError {
+        call: call.to_string(),
+        result: format!("{:?}", result),
+    });
+}
+
+/// Get the `deviceUUID` of a device. `vkGetPhysicalDeviceProperties2` is core in Vulkan 1.1, so
+/// this needs both the instance and the device to be at least 1.1.
+fn device_uuid(
+    instance: &ash::Instance,
+    instance_api_version: u32,
+    physical_device: vk::PhysicalDevice,
+    properties: &vk::PhysicalDeviceProperties,
+) -> Option<[u8; vk::UUID_SIZE]> {
+    if instance_api_version < vk::API_VERSION_1_1 {
+        log::warn!(
+            "  Device UUIDs can't be queried with a Vulkan {} instance",
+            requirements::format_api_version(instance_api_version)
+        );
+        return None;
+    }
+
+    if properties.api_version < vk::API_VERSION_1_1 {
+        return None;
+    }
+
+    let mut id_properties = vk::PhysicalDeviceIDProperties::default();
+
+    let mut properties_2 = vk::PhysicalDeviceProperties2::builder().push_next(&mut id_properties);
+
+    unsafe { instance.get_physical_device_properties2(physical_device, &mut properties_2) };
+
+    Some(id_properties.device_uuid)
 }
 
 fn tick(supported: bool) -> &'static str {
@@ -161,7 +637,7 @@ unsafe fn cstr_from_array(array: &[c_char]) -> &CStr {
 }
 
 pub fn load_shader_module(bytes: &[u8], device: &ash::Device) -> anyhow::Result<vk::ShaderModule> {
-    let spv = ash::util::read_spv(&mut std::io::Cursor::new(bytes))?;
+    let spv = read_spirv(bytes)?;
     Ok(unsafe {
         device.create_shader_module(&vk::ShaderModuleCreateInfo::builder().code(&spv), None)
     }?)
@@ -181,31 +657,129 @@ pub fn load_shader_module_as_stage<'a>(
         .name(entry_point))
 }
 
+/// The type of a vertex attribute. Most variants take up a single location; see
+/// [`Self::location_count`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum VertexAttribute {
     Uint,
     Float,
     Vec2,
     Vec3,
     Vec4,
+    Int,
+    IVec2,
+    IVec3,
+    IVec4,
+    UVec2,
+    UVec3,
+    UVec4,
+    /// A `vec4` read from four normalized bytes, such as a colour.
+    Unorm8x4,
+    /// A `vec4` read from four signed normalized bytes.
+    Snorm8x4,
+    /// A `vec2` read from two normalized 16-bit integers.
+    Unorm16x2,
+    /// A `vec4` read from four normalized 16-bit integers.
+    Unorm16x4,
+    /// A `vec2` read from two signed normalized 16-bit integers.
+    Snorm16x2,
+    /// A `vec4` read from four signed normalized 16-bit integers, such as a padded normal.
+    Snorm16x4,
+    /// A `vec2` read from two half floats, such as UVs.
+    Half2,
+    /// A `vec4` read from four half floats.
+    Half4,
+    /// A `uvec4` read from four bytes.
+    Uint8x4,
+    /// A `uvec2` read from two 16-bit integers.
+    Uint16x2,
+    /// A `uvec4` read from four 16-bit integers, such as joint indices.
+    Uint16x4,
+    /// A `vec4` read from a packed 2_10_10_10 word, with `x` in the lowest 10 bits and `w` in the
+    /// highest 2 bits, such as a tangent and its handedness.
+    UnormA2B10G10R10,
+    /// The signed normalized version of [`Self::UnormA2B10G10R10`].
+    SnormA2B10G10R10,
+    /// A column-major `mat4`, taking up four consecutive locations, such as an instance transform.
+    Mat4,
 }
 
 impl VertexAttribute {
-    fn size(&self) -> u32 {
+    /// The size of the attribute in bytes.
+    pub const fn size(&self) -> u32 {
         match self {
-            Self::Float | Self::Uint => 4,
-            Self::Vec2 => 8,
-            Self::Vec3 => 12,
-            Self::Vec4 => 16,
+            Self::Float | Self::Uint | Self::Int => 4,
+            Self::Vec2 | Self::IVec2 | Self::UVec2 => 8,
+            Self::Vec3 | Self::IVec3 | Self::UVec3 => 12,
+            Self::Vec4 | Self::IVec4 | Self::UVec4 => 16,
+            Self::Unorm8x4 | Self::Snorm8x4 | Self::Uint8x4 => 4,
+            Self::Unorm16x2 | Self::Snorm16x2 | Self::Half2 | Self::Uint16x2 => 4,
+            Self::Unorm16x4 | Self::Snorm16x4 | Self::Half4 | Self::Uint16x4 => 8,
+            Self::UnormA2B10G10R10 | Self::SnormA2B10G10R10 => 4,
+            Self::Mat4 => 64,
         }
     }
 
+    /// The format of each location.
     fn format(&self) -> vk::Format {
         match self {
             Self::Uint => vk::Format::R32_UINT,
             Self::Float => vk::Format::R32_SFLOAT,
             Self::Vec2 => vk::Format::R32G32_SFLOAT,
             Self::Vec3 => vk::Format::R32G32B32_SFLOAT,
-            Self::Vec4 => vk::Format::R32G32B32A32_SFLOAT,
+            Self::Vec4 | Self::Mat4 => vk::Format::R32G32B32A32_SFLOAT,
+            Self::Int => vk::Format::R32_SINT,
+            Self::IVec2 => vk::Format::R32G32_SINT,
+            Self::IVec3 => vk::Format::R32G32B32_SINT,
+            Self::IVec4 => vk::Format::R32G32B32A32_SINT,
+            Self::UVec2 => vk::Format::R32G32_UINT,
+            Self::UVec3 => vk::Format::R32G32B32_UINT,
+            Self::UVec4 => vk::Format::R32G32B32A32_UINT,
+            Self::Unorm8x4 => vk::Format::R8G8B8A8_UNORM,
+            Self::Snorm8x4 => vk::Format::R8G8B8A8_SNORM,
+            Self::Unorm16x2 => vk::Format::R16G16_UNORM,
+            Self::Unorm16x4 => vk::Format::R16G16B16A16_UNORM,
+            Self::Snorm16x2 => vk::Format::R16G16_SNORM,
+            Self::Snorm16x4 => vk::Format::R16G16B16A16_SNORM,
+            Self::Half2 => vk::Format::R16G16_SFLOAT,
+            Self::Half4 => vk::Format::R16G16B16A16_SFLOAT,
+            Self::Uint8x4 => vk::Format::R8G8B8A8_UINT,
+            Self::Uint16x2 => vk::Format::R16G16_UINT,
+            Self::Uint16x4 => vk::Format::R16G16B16A16_UINT,
+            Self::UnormA2B10G10R10 => vk::Format::A2B10G10R10_UNORM_PACK32,
+            Self::SnormA2B10G10R10 => vk::Format::A2B10G10R10_SNORM_PACK32,
+        }
+    }
+
+    /// The number of consecutive shader locations that the attribute takes up.
+    pub fn location_count(&self) -> u32 {
+        match self {
+            Self::Mat4 => 4,
+            _ => 1,
+        }
+    }
+
+    /// Push a description for each location of the attribute, advancing `location`.
+    fn push_descriptions(
+        &self,
+        binding: u32,
+        offset: u32,
+        location: &mut u32,
+        descriptions: &mut Vec<vk::VertexInputAttributeDescription>,
+    ) {
+        let location_count = self.location_count();
+        let location_size = self.size() / location_count;
+
+        for i in 0..location_count {
+            descriptions.push(
+                *vk::VertexInputAttributeDescription::builder()
+                    .binding(binding)
+                    .location(*location)
+                    .format(self.format())
+                    .offset(offset + i * location_size),
+            );
+
+            *location += 1;
         }
     }
 }
@@ -221,59 +795,14 @@ pub fn create_vertex_attribute_descriptions(
         let mut offset = 0;
 
         for attribute in attributes.iter() {
-            descriptions.push(
-                *vk::VertexInputAttributeDescription::builder()
-                    .binding(binding as u32)
-                    .location(location)
-                    .format(attribute.format())
-                    .offset(offset),
-            );
-
+            attribute.push_descriptions(binding as u32, offset, &mut location, &mut descriptions);
             offset += attribute.size();
-            location += 1;
         }
     }
 
     descriptions
 }
 
-/// A callback for the [Vulkan Debug Utils Messenger](https://docs.rs/ash/0.33.3+1.2.191/ash/vk/struct.DebugUtilsMessengerEXT.html)
-///
-/// # Safety
-///
-/// Don't use this in any way except as an input to [`DebugUtilsMessengerCreateInfoEXTBuilder.pfn_user_callback`](https://docs.rs/ash/0.33.3+1.2.191/ash/vk/struct.DebugUtilsMessengerCreateInfoEXTBuilder.html#method.pfn_user_callback).
-///
-pub unsafe extern "system" fn vulkan_debug_utils_callback(
-    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
-    message_type: vk::DebugUtilsMessageTypeFlagsEXT,
-    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _p_user_data: *mut std::ffi::c_void,
-) -> vk::Bool32 {
-    let filter_out = (message_severity == vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
-        && message_type == vk::DebugUtilsMessageTypeFlagsEXT::GENERAL)
-        || (message_severity == vk::DebugUtilsMessageSeverityFlagsEXT::INFO
-            && message_type == vk::DebugUtilsMessageTypeFlagsEXT::GENERAL)
-        || (message_severity == vk::DebugUtilsMessageSeverityFlagsEXT::INFO
-            && message_type == vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION);
-
-    let level = if filter_out {
-        log::Level::Trace
-    } else {
-        match message_severity {
-            vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE => log::Level::Debug,
-            vk::DebugUtilsMessageSeverityFlagsEXT::INFO => log::Level::Info,
-            vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => log::Level::Warn,
-            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => log::Level::Error,
-            _ => log::Level::Info,
-        }
-    };
-
-    let message = std::ffi::CStr::from_ptr((*p_callback_data).p_message);
-    let ty = format!("{:?}", message_type).to_lowercase();
-    log::log!(level, "[Debug Msg][{}] {:?}", ty, message);
-    vk::FALSE
-}
-
 pub struct PrimitiveState {
     pub cull_mode: vk::CullModeFlags,
     pub topology: vk::PrimitiveTopology,
@@ -295,7 +824,7 @@ pub struct GraphicsPipelineDescriptor<'a> {
 }
 
 impl<'a> GraphicsPipelineDescriptor<'a> {
-    pub fn as_baked(&self) -> BakedGraphicsPipelineDescriptor {
+    pub fn as_baked(&self) -> BakedGraphicsPipelineDescriptor<'_> {
         BakedGraphicsPipelineDescriptor {
             input_assembly: vk::PipelineInputAssemblyStateCreateInfo::builder()
                 .topology(self.primitive_state.topology),
@@ -378,7 +907,7 @@ pub fn set_object_name<T: vk::Handle>(
     unsafe {
         debug_utils_loader.debug_utils_set_object_name(
             device.handle(),
-            &*vk::DebugUtilsObjectNameInfoEXT::builder()
+            &vk::DebugUtilsObjectNameInfoEXT::builder()
                 .object_type(T::TYPE)
                 .object_handle(handle.as_raw())
                 .object_name(&name),
@@ -990,6 +1519,7 @@ impl Swapchain {
 }
 
 // The top mip must be in a transfer src layout and the rest must be in transfer dst.
+#[allow(clippy::too_many_arguments)]
 pub fn generate_mips(
     device: &ash::Device,
     command_buffer: vk::CommandBuffer,
@@ -1093,3 +1623,127 @@ pub fn generate_mips(
         height = (height / 2).max(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cstr(bytes: &[u8]) -> &CStr {
+        CStr::from_bytes_with_nul(bytes).unwrap()
+    }
+
+    fn strings(list: &OwnedCStrList) -> Vec<&str> {
+        list.iter().map(|cstr| cstr.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn owned_cstr_list_ignores_duplicates() {
+        let mut list = OwnedCStrList::new();
+
+        assert!(list.push_str("VK_KHR_swapchain").unwrap());
+        assert!(!list.push_str("VK_KHR_swapchain").unwrap());
+        assert!(!list.push_cstr(cstr(b"VK_KHR_swapchain\0")));
+        assert!(list.push_cstr(cstr(b"VK_EXT_memory_budget\0")));
+
+        assert_eq!(strings(&list), ["VK_KHR_swapchain", "VK_EXT_memory_budget"]);
+        assert_eq!(list.pointers().len(), 2);
+        assert!(list.contains_str("VK_EXT_memory_budget"));
+        assert!(!list.contains_str("VK_EXT"));
+    }
+
+    #[test]
+    fn owned_cstr_list_pointers_match_the_strings() {
+        let list = OwnedCStrList::from_strs((0..64).map(|i| format!("extension_{}", i))).unwrap();
+
+        for (pointer, string) in list.pointers().iter().zip(list.iter()) {
+            assert_eq!(unsafe { CStr::from_ptr(*pointer) }, string);
+        }
+
+        let clone = list.clone();
+
+        for (pointer, string) in clone.pointers().iter().zip(clone.iter()) {
+            assert_eq!(unsafe { CStr::from_ptr(*pointer) }, string);
+        }
+    }
+
+    #[test]
+    fn owned_cstr_list_merge_keeps_order() {
+        let mut list = OwnedCStrList::from_strs(["a", "b"]).unwrap();
+        let other = OwnedCStrList::from_strs(["b", "c", "a", "d"]).unwrap();
+
+        list.merge(&other);
+
+        assert_eq!(strings(&list), ["a", "b", "c", "d"]);
+
+        let borrowed = CStrList::new(vec![cstr(b"x\0"), cstr(b"x\0"), cstr(b"y\0")]);
+        assert_eq!(strings(&OwnedCStrList::from(&borrowed)), ["x", "y"]);
+    }
+
+    #[test]
+    fn owned_cstr_list_rejects_interior_nuls() {
+        assert!(OwnedCStrList::new().push_str("bad\0name").is_err());
+    }
+
+    #[test]
+    fn owned_cstr_list_from_env() {
+        // Unique names, since tests share the process environment.
+        std::env::set_var(
+            "AOA_TEST_OWNED_CSTR_LIST",
+            " VK_KHR_swapchain, ,VK_EXT_memory_budget,VK_KHR_swapchain,",
+        );
+
+        let list = OwnedCStrList::from_env("AOA_TEST_OWNED_CSTR_LIST").unwrap();
+        assert_eq!(strings(&list), ["VK_KHR_swapchain", "VK_EXT_memory_budget"]);
+
+        let list = OwnedCStrList::from_env("AOA_TEST_OWNED_CSTR_LIST_UNSET").unwrap();
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn vertex_attribute_descriptions() {
+        let descriptions = create_vertex_attribute_descriptions(&[
+            &[
+                VertexAttribute::Vec3,
+                VertexAttribute::SnormA2B10G10R10,
+                VertexAttribute::Snorm16x4,
+                VertexAttribute::Half2,
+                VertexAttribute::Unorm8x4,
+                VertexAttribute::Uint16x4,
+            ],
+            &[VertexAttribute::Mat4, VertexAttribute::Uint],
+        ]);
+
+        let descriptions: Vec<_> = descriptions
+            .iter()
+            .map(|description| {
+                (
+                    description.binding,
+                    description.location,
+                    description.format,
+                    description.offset,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            descriptions,
+            [
+                (0, 0, vk::Format::R32G32B32_SFLOAT, 0),
+                (0, 1, vk::Format::A2B10G10R10_SNORM_PACK32, 12),
+                (0, 2, vk::Format::R16G16B16A16_SNORM, 16),
+                (0, 3, vk::Format::R16G16_SFLOAT, 24),
+                (0, 4, vk::Format::R8G8B8A8_UNORM, 28),
+                (0, 5, vk::Format::R16G16B16A16_UINT, 32),
+                (1, 6, vk::Format::R32G32B32A32_SFLOAT, 0),
+                (1, 7, vk::Format::R32G32B32A32_SFLOAT, 16),
+                (1, 8, vk::Format::R32G32B32A32_SFLOAT, 32),
+                (1, 9, vk::Format::R32G32B32A32_SFLOAT, 48),
+                (1, 10, vk::Format::R32_UINT, 64),
+            ]
+        );
+
+        assert_eq!(VertexAttribute::Mat4.location_count(), 4);
+        assert_eq!(VertexAttribute::Mat4.size(), 64);
+        assert_eq!(VertexAttribute::Uint16x4.size(), 8);
+    }
+}
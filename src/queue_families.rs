@@ -0,0 +1,215 @@
+use ash::vk;
+
+/// A queue family index along with the properties that are useful after selection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueFamily {
+    pub index: u32,
+    pub queue_count: u32,
+    pub queue_flags: vk::QueueFlags,
+    /// The number of meaningful bits in timestamps written on this family. 0 means timestamps
+    /// aren't supported.
+    pub timestamp_valid_bits: u32,
+}
+
+impl QueueFamily {
+    fn new(index: usize, properties: &vk::QueueFamilyProperties) -> Self {
+        Self {
+            index: index as u32,
+            queue_count: properties.queue_count,
+            queue_flags: properties.queue_flags,
+            timestamp_valid_bits: properties.timestamp_valid_bits,
+        }
+    }
+}
+
+/// The queue families selected for a device.
+///
+/// `compute` and `transfer` fall back to the graphics family if the device doesn't have
+/// dedicated families for them. For headless selection, a device without any graphics family
+/// uses a compute family in place of the graphics one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueFamilies {
+    /// A family that supports graphics and (if a surface was given) presentation, or a compute
+    /// family if `allow_compute_only` was set and there is none. See
+    /// [`has_graphics`](Self::has_graphics).
+    pub graphics: QueueFamily,
+    /// A compute family without graphics support, for async compute.
+    pub compute: QueueFamily,
+    /// A family with neither graphics nor compute support, for uploads.
+    pub transfer: QueueFamily,
+}
+
+impl QueueFamilies {
+    pub(crate) fn find(
+        queue_family_properties: &[vk::QueueFamilyProperties],
+        mut supports_present: impl FnMut(u32) -> Result<bool, vk::Result>,
+        allow_compute_only: bool,
+    ) -> Result<Option<Self>, vk::Result> {
+        let mut graphics = None;
+
+        for (i, properties) in queue_family_properties.iter().enumerate() {
+            if properties.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+                && supports_present(i as u32)?
+            {
+                graphics = Some(QueueFamily::new(i, properties));
+                break;
+            }
+        }
+
+        let find_family = |include: vk::QueueFlags, exclude: vk::QueueFlags| {
+            queue_family_properties
+                .iter()
+                .enumerate()
+                .find(|(_, properties)| {
+                    properties.queue_flags.contains(include)
+                        && !properties.queue_flags.intersects(exclude)
+                })
+                .map(|(i, properties)| QueueFamily::new(i, properties))
+        };
+
+        let graphics = match graphics {
+            Some(graphics) => graphics,
+            None if allow_compute_only => {
+                match find_family(vk::QueueFlags::COMPUTE, vk::QueueFlags::empty()) {
+                    Some(compute) => compute,
+                    None => return Ok(None),
+                }
+            }
+            None => return Ok(None),
+        };
+
+        let compute =
+            find_family(vk::QueueFlags::COMPUTE, vk::QueueFlags::GRAPHICS).unwrap_or(graphics);
+
+        let transfer = find_family(
+            vk::QueueFlags::TRANSFER,
+            vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE,
+        )
+        .unwrap_or(graphics);
+
+        Ok(Some(Self {
+            graphics,
+            compute,
+            transfer,
+        }))
+    }
+
+    /// `false` for compute-only devices selected without a surface.
+    pub fn has_graphics(&self) -> bool {
+        self.graphics.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+    }
+
+    pub fn has_async_compute(&self) -> bool {
+        self.compute.index != self.graphics.index
+    }
+
+    pub fn has_dedicated_transfer(&self) -> bool {
+        self.transfer.index != self.graphics.index
+    }
+
+    /// The distinct family indices, in graphics, compute, transfer order. Useful for
+    /// `vk::DeviceQueueCreateInfo`s and concurrent sharing modes.
+    pub fn unique_indices(&self) -> Vec<u32> {
+        let mut indices = Vec::with_capacity(3);
+
+        for family in [self.graphics, self.compute, self.transfer] {
+            if !indices.contains(&family.index) {
+                indices.push(family.index);
+            }
+        }
+
+        indices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn family(queue_flags: vk::QueueFlags) -> vk::QueueFamilyProperties {
+        vk::QueueFamilyProperties {
+            queue_flags,
+            queue_count: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn finds_dedicated_families() {
+        let families = [
+            family(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER),
+            family(vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER),
+            family(vk::QueueFlags::TRANSFER),
+        ];
+
+        let queue_families = QueueFamilies::find(&families, |_| Ok(true), false)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(queue_families.graphics.index, 0);
+        assert_eq!(queue_families.compute.index, 1);
+        assert_eq!(queue_families.transfer.index, 2);
+        assert_eq!(queue_families.unique_indices(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn falls_back_to_the_graphics_family() {
+        let families = [family(
+            vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER,
+        )];
+
+        let queue_families = QueueFamilies::find(&families, |_| Ok(true), false)
+            .unwrap()
+            .unwrap();
+
+        assert!(!queue_families.has_async_compute());
+        assert!(!queue_families.has_dedicated_transfer());
+        assert_eq!(queue_families.unique_indices(), [0]);
+    }
+
+    #[test]
+    fn skips_families_without_present_support() {
+        let families = [
+            family(vk::QueueFlags::GRAPHICS),
+            family(vk::QueueFlags::GRAPHICS),
+        ];
+
+        let queue_families = QueueFamilies::find(&families, |i| Ok(i == 1), false)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(queue_families.graphics.index, 1);
+    }
+
+    #[test]
+    fn compute_only_devices_need_allow_compute_only() {
+        let families = [
+            family(vk::QueueFlags::TRANSFER),
+            family(vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER),
+        ];
+
+        assert_eq!(
+            QueueFamilies::find(&families, |_| Ok(true), false).unwrap(),
+            None
+        );
+
+        let queue_families = QueueFamilies::find(&families, |_| Ok(true), true)
+            .unwrap()
+            .unwrap();
+
+        assert!(!queue_families.has_graphics());
+        assert_eq!(queue_families.graphics.index, 1);
+        assert_eq!(queue_families.compute.index, 1);
+        assert_eq!(queue_families.transfer.index, 0);
+    }
+
+    #[test]
+    fn devices_without_graphics_or_compute_are_rejected() {
+        let families = [family(vk::QueueFlags::TRANSFER)];
+
+        assert_eq!(
+            QueueFamilies::find(&families, |_| Ok(true), true).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,1599 @@
+//! A small SPIR-V reflector that extracts descriptor bindings and push constant blocks.
+
+use crate::spirv::{check_header, HEADER_WORDS};
+use ash::vk;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+mod op {
+    pub const NAME: u16 = 5;
+    pub const ENTRY_POINT: u16 = 15;
+    pub const TYPE_VOID: u16 = 19;
+    pub const TYPE_BOOL: u16 = 20;
+    pub const TYPE_INT: u16 = 21;
+    pub const TYPE_FLOAT: u16 = 22;
+    pub const TYPE_VECTOR: u16 = 23;
+    pub const TYPE_MATRIX: u16 = 24;
+    pub const TYPE_IMAGE: u16 = 25;
+    pub const TYPE_SAMPLER: u16 = 26;
+    pub const TYPE_SAMPLED_IMAGE: u16 = 27;
+    pub const TYPE_ARRAY: u16 = 28;
+    pub const TYPE_RUNTIME_ARRAY: u16 = 29;
+    pub const TYPE_STRUCT: u16 = 30;
+    pub const TYPE_POINTER: u16 = 32;
+    pub const CONSTANT: u16 = 43;
+    pub const SPEC_CONSTANT: u16 = 50;
+    pub const FUNCTION: u16 = 54;
+    pub const FUNCTION_END: u16 = 56;
+    pub const VARIABLE: u16 = 59;
+    pub const DECORATE: u16 = 71;
+    pub const MEMBER_DECORATE: u16 = 72;
+    pub const TYPE_ACCELERATION_STRUCTURE: u16 = 5341;
+}
+
+mod decoration {
+    pub const BLOCK: u32 = 2;
+    pub const BUFFER_BLOCK: u32 = 3;
+    pub const ARRAY_STRIDE: u32 = 6;
+    pub const MATRIX_STRIDE: u32 = 7;
+    pub const LOCATION: u32 = 30;
+    pub const BINDING: u32 = 33;
+    pub const DESCRIPTOR_SET: u32 = 34;
+    pub const OFFSET: u32 = 35;
+}
+
+mod storage_class {
+    pub const UNIFORM_CONSTANT: u32 = 0;
+    pub const INPUT: u32 = 1;
+    pub const UNIFORM: u32 = 2;
+    pub const PUSH_CONSTANT: u32 = 9;
+    pub const STORAGE_BUFFER: u32 = 12;
+}
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+#[derive(Clone, Debug)]
+enum Type {
+    Void,
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+    AccelerationStructure,
+}
+
+#[derive(Default)]
+struct Decorations {
+    descriptor_set: Option<u32>,
+    binding: Option<u32>,
+    block: bool,
+    buffer_block: bool,
+    array_stride: Option<u32>,
+    location: Option<u32>,
+}
+
+#[derive(Default)]
+struct MemberDecorations {
+    offset: Option<u32>,
+    matrix_stride: Option<u32>,
+}
+
+/// The raw parsed contents of a module that reflection cares about.
+#[derive(Default)]
+pub(crate) struct Module {
+    /// The (major, minor) SPIR-V version.
+    version: (u32, u32),
+    names: HashMap<u32, String>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), MemberDecorations>,
+    /// (result type, id, storage class)
+    variables: Vec<(u32, u32, u32)>,
+    entry_points: Vec<EntryPoint>,
+    /// Every operand of every instruction in each function, keyed by function id. Used to find
+    /// the variables and functions that a function references.
+    function_operands: HashMap<u32, Vec<u32>>,
+}
+
+/// Read a nul-terminated, word-packed SPIR-V literal string.
+fn read_string(words: &[u32]) -> (String, usize) {
+    let mut bytes = Vec::new();
+
+    for (i, word) in words.iter().enumerate() {
+        for byte in word.to_le_bytes() {
+            if byte == 0 {
+                return (String::from_utf8_lossy(&bytes).into_owned(), i + 1);
+            }
+
+            bytes.push(byte);
+        }
+    }
+
+    (String::from_utf8_lossy(&bytes).into_owned(), words.len())
+}
+
+fn execution_model_to_stage(execution_model: u32) -> vk::ShaderStageFlags {
+    match execution_model {
+        0 => vk::ShaderStageFlags::VERTEX,
+        1 => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+        2 => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+        3 => vk::ShaderStageFlags::GEOMETRY,
+        4 => vk::ShaderStageFlags::FRAGMENT,
+        5 => vk::ShaderStageFlags::COMPUTE,
+        5267 => vk::ShaderStageFlags::TASK_NV,
+        5268 => vk::ShaderStageFlags::MESH_NV,
+        5313 => vk::ShaderStageFlags::RAYGEN_KHR,
+        5314 => vk::ShaderStageFlags::INTERSECTION_KHR,
+        5315 => vk::ShaderStageFlags::ANY_HIT_KHR,
+        5316 => vk::ShaderStageFlags::CLOSEST_HIT_KHR,
+        5317 => vk::ShaderStageFlags::MISS_KHR,
+        5318 => vk::ShaderStageFlags::CALLABLE_KHR,
+        _ => vk::ShaderStageFlags::empty(),
+    }
+}
+
+impl Module {
+    pub(crate) fn parse(spv: &[u32]) -> anyhow::Result<Self> {
+        check_header(spv)?;
+
+        let mut module = Self {
+            version: ((spv[1] >> 16) & 0xff, (spv[1] >> 8) & 0xff),
+            ..Default::default()
+        };
+        let mut current_function = None;
+        let mut words = &spv[HEADER_WORDS..];
+
+        while !words.is_empty() {
+            let opcode = (words[0] & 0xffff) as u16;
+            let word_count = (words[0] >> 16) as usize;
+
+            if word_count == 0 || word_count > words.len() {
+                return Err(anyhow::anyhow!(
+                    "Malformed instruction (opcode {}) at word {}",
+                    opcode,
+                    spv.len() - words.len()
+                ));
+            }
+
+            let operands = &words[1..word_count];
+            words = &words[word_count..];
+
+            match opcode {
+                op::FUNCTION => current_function = operands.get(1).copied(),
+                op::FUNCTION_END => current_function = None,
+                _ => {
+                    if let Some(function) = current_function {
+                        module
+                            .function_operands
+                            .entry(function)
+                            .or_default()
+                            .extend_from_slice(operands);
+                    }
+                }
+            }
+
+            module.parse_instruction(opcode, operands).ok_or_else(|| {
+                anyhow::anyhow!("Truncated operands for instruction with opcode {}", opcode)
+            })?;
+        }
+
+        Ok(module)
+    }
+
+    fn parse_instruction(&mut self, opcode: u16, operands: &[u32]) -> Option<()> {
+        let operand = |i: usize| operands.get(i).copied();
+
+        match opcode {
+            op::NAME => {
+                let (name, _) = read_string(operands.get(1..)?);
+                self.names.insert(operand(0)?, name);
+            }
+            op::ENTRY_POINT => {
+                let (name, name_words) = read_string(operands.get(2..)?);
+
+                self.entry_points.push(EntryPoint {
+                    name,
+                    stage: execution_model_to_stage(operand(0)?),
+                    function: operand(1)?,
+                    interface: operands.get(2 + name_words..)?.to_vec(),
+                });
+            }
+            op::TYPE_VOID => {
+                self.types.insert(operand(0)?, Type::Void);
+            }
+            op::TYPE_BOOL => {
+                self.types.insert(operand(0)?, Type::Bool);
+            }
+            op::TYPE_INT => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::Int {
+                        width: operand(1)?,
+                        signed: operand(2)? != 0,
+                    },
+                );
+            }
+            op::TYPE_FLOAT => {
+                self.types
+                    .insert(operand(0)?, Type::Float { width: operand(1)? });
+            }
+            op::TYPE_VECTOR => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::Vector {
+                        component: operand(1)?,
+                        count: operand(2)?,
+                    },
+                );
+            }
+            op::TYPE_MATRIX => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::Matrix {
+                        column: operand(1)?,
+                        count: operand(2)?,
+                    },
+                );
+            }
+            op::TYPE_IMAGE => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::Image {
+                        dim: operand(2)?,
+                        sampled: operand(6)?,
+                    },
+                );
+            }
+            op::TYPE_SAMPLER => {
+                self.types.insert(operand(0)?, Type::Sampler);
+            }
+            op::TYPE_SAMPLED_IMAGE => {
+                self.types.insert(operand(0)?, Type::SampledImage);
+            }
+            op::TYPE_ARRAY => {
+                // Array lengths are constants, which are always declared before use.
+                let length = self.constants.get(&operand(2)?).copied().unwrap_or(1);
+
+                self.types.insert(
+                    operand(0)?,
+                    Type::Array {
+                        element: operand(1)?,
+                        length,
+                    },
+                );
+            }
+            op::TYPE_RUNTIME_ARRAY => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::RuntimeArray {
+                        element: operand(1)?,
+                    },
+                );
+            }
+            op::TYPE_STRUCT => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::Struct {
+                        members: operands[1..].to_vec(),
+                    },
+                );
+            }
+            op::TYPE_POINTER => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::Pointer {
+                        pointee: operand(2)?,
+                    },
+                );
+            }
+            op::TYPE_ACCELERATION_STRUCTURE => {
+                self.types.insert(operand(0)?, Type::AccelerationStructure);
+            }
+            // Only 32-bit integer constants are interesting, as they're used for array lengths.
+            op::CONSTANT | op::SPEC_CONSTANT => {
+                if let Some(Type::Int { width: 32, .. }) = self.types.get(&operand(0)?) {
+                    self.constants.insert(operand(1)?, operand(2)?);
+                }
+            }
+            op::VARIABLE => {
+                self.variables.push((operand(0)?, operand(1)?, operand(2)?));
+            }
+            op::DECORATE => {
+                let decorations = self.decorations.entry(operand(0)?).or_default();
+
+                match operand(1)? {
+                    decoration::BLOCK => decorations.block = true,
+                    decoration::BUFFER_BLOCK => decorations.buffer_block = true,
+                    decoration::ARRAY_STRIDE => decorations.array_stride = Some(operand(2)?),
+                    decoration::LOCATION => decorations.location = Some(operand(2)?),
+                    decoration::BINDING => decorations.binding = Some(operand(2)?),
+                    decoration::DESCRIPTOR_SET => decorations.descriptor_set = Some(operand(2)?),
+                    _ => {}
+                }
+            }
+            op::MEMBER_DECORATE => {
+                let decorations = self
+                    .member_decorations
+                    .entry((operand(0)?, operand(1)?))
+                    .or_default();
+
+                match operand(2)? {
+                    decoration::OFFSET => decorations.offset = Some(operand(3)?),
+                    decoration::MATRIX_STRIDE => decorations.matrix_stride = Some(operand(3)?),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+
+        Some(())
+    }
+
+    /// The ids of the global variables that an entry point statically uses.
+    fn used_globals(&self, entry_point: &EntryPoint) -> HashSet<u32> {
+        // From SPIR-V 1.4, the interface lists every global that the entry point uses.
+        if self.version >= (1, 4) {
+            return entry_point.interface.iter().copied().collect();
+        }
+
+        // Before that it only lists inputs and outputs, so walk the call graph instead. Every
+        // operand is treated as a potential id, which can only over-approximate the result.
+        let mut used = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![entry_point.function];
+
+        while let Some(function) = pending.pop() {
+            if !visited.insert(function) {
+                continue;
+            }
+
+            for &operand in self.function_operands.get(&function).into_iter().flatten() {
+                if self.function_operands.contains_key(&operand) {
+                    pending.push(operand);
+                } else {
+                    used.insert(operand);
+                }
+            }
+        }
+
+        used.extend(&entry_point.interface);
+        used
+    }
+
+    fn ty(&self, id: u32) -> anyhow::Result<&Type> {
+        self.types
+            .get(&id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown type id {}", id))
+    }
+
+    /// The size of a type in bytes, following the explicit layout decorations.
+    fn size_of(&self, id: u32, matrix_stride: Option<u32>) -> anyhow::Result<u32> {
+        Ok(match self.ty(id)? {
+            Type::Bool => 4,
+            Type::Int { width, .. } | Type::Float { width } => width / 8,
+            Type::Vector { component, count } => self.size_of(*component, None)? * count,
+            Type::Matrix { column, count } => match matrix_stride {
+                Some(matrix_stride) => matrix_stride * count,
+                None => self.size_of(*column, None)? * count,
+            },
+            Type::Array { element, length } => {
+                let stride = match self.decorations.get(&id).and_then(|d| d.array_stride) {
+                    Some(stride) => stride,
+                    None => self.size_of(*element, matrix_stride)?,
+                };
+
+                stride * length
+            }
+            Type::RuntimeArray { .. } => 0,
+            Type::Struct { members } => {
+                let mut size = 0;
+
+                for (i, &member) in members.iter().enumerate() {
+                    let member_decorations = self.member_decorations.get(&(id, i as u32));
+                    let offset = member_decorations.and_then(|d| d.offset).unwrap_or(size);
+                    let member_size =
+                        self.size_of(member, member_decorations.and_then(|d| d.matrix_stride))?;
+
+                    size = size.max(offset + member_size);
+                }
+
+                size
+            }
+            other => return Err(anyhow::anyhow!("Type {:?} does not have a size", other)),
+        })
+    }
+
+    fn shader_input(
+        &self,
+        id: u32,
+        location: u32,
+        name: Option<String>,
+    ) -> anyhow::Result<ShaderInput> {
+        let mut location_count = 1;
+        let mut id = id;
+
+        // Arrays and matrices take up one location per element or column.
+        loop {
+            match self.ty(id)? {
+                Type::Array { element, length } => {
+                    location_count *= length;
+                    id = *element;
+                }
+                Type::Matrix { column, count } => {
+                    location_count *= count;
+                    id = *column;
+                }
+                _ => break,
+            }
+        }
+
+        let (component, component_count) = match self.ty(id)? {
+            Type::Vector { component, count } => (*component, *count),
+            _ => (id, 1),
+        };
+
+        let (numeric_type, bit_width) = match self.ty(component)? {
+            Type::Float { width } => (NumericType::Float, *width),
+            Type::Int {
+                width,
+                signed: true,
+            } => (NumericType::Sint, *width),
+            Type::Int {
+                width,
+                signed: false,
+            } => (NumericType::Uint, *width),
+            other => {
+                return Err(anyhow::anyhow!(
+                    "Input at location {} has an unsupported type {:?}",
+                    location,
+                    other
+                ))
+            }
+        };
+
+        // 64-bit vectors with more than two components take up two locations each.
+        if bit_width == 64 && component_count > 2 {
+            location_count *= 2;
+        }
+
+        Ok(ShaderInput {
+            location,
+            name,
+            numeric_type,
+            bit_width,
+            component_count,
+            location_count,
+        })
+    }
+
+    fn descriptor_type(
+        &self,
+        storage_class: u32,
+        id: u32,
+    ) -> anyhow::Result<Option<vk::DescriptorType>> {
+        let decorations = self.decorations.get(&id);
+
+        Ok(match (storage_class, self.ty(id)?) {
+            (storage_class::UNIFORM_CONSTANT, Type::Sampler) => Some(vk::DescriptorType::SAMPLER),
+            (storage_class::UNIFORM_CONSTANT, Type::SampledImage) => {
+                Some(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            }
+            (storage_class::UNIFORM_CONSTANT, Type::Image { dim, sampled }) => {
+                Some(match (*dim, *sampled) {
+                    (DIM_SUBPASS_DATA, _) => vk::DescriptorType::INPUT_ATTACHMENT,
+                    (DIM_BUFFER, 2) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+                    (DIM_BUFFER, _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+                    (_, 2) => vk::DescriptorType::STORAGE_IMAGE,
+                    (_, _) => vk::DescriptorType::SAMPLED_IMAGE,
+                })
+            }
+            (storage_class::UNIFORM_CONSTANT, Type::AccelerationStructure) => {
+                Some(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
+            }
+            (storage_class::UNIFORM, Type::Struct { .. }) => {
+                if decorations.is_some_and(|d| d.buffer_block) {
+                    Some(vk::DescriptorType::STORAGE_BUFFER)
+                } else {
+                    Some(vk::DescriptorType::UNIFORM_BUFFER)
+                }
+            }
+            (storage_class::STORAGE_BUFFER, Type::Struct { .. }) => {
+                Some(vk::DescriptorType::STORAGE_BUFFER)
+            }
+            _ => None,
+        })
+    }
+}
+
+/// An entry point declared in a module.
+#[derive(Clone, Debug)]
+pub struct EntryPoint {
+    pub name: String,
+    pub stage: vk::ShaderStageFlags,
+    /// The id of the entry point's function.
+    function: u32,
+    /// The ids of the input and output variables that the entry point uses, and from SPIR-V 1.4,
+    /// every other global variable that it uses.
+    interface: Vec<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    /// The number of descriptors. 0 for runtime-sized arrays.
+    pub count: u32,
+    pub stages: vk::ShaderStageFlags,
+    pub name: Option<String>,
+}
+
+impl DescriptorBinding {
+    pub fn is_runtime_array(&self) -> bool {
+        self.count == 0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PushConstantBlock {
+    /// The offset of the first member of the block.
+    pub offset: u32,
+    /// The size of the block, starting at `offset`.
+    pub size: u32,
+    pub stages: vk::ShaderStageFlags,
+    pub name: Option<String>,
+}
+
+/// The component type of a shader input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumericType {
+    Float,
+    Sint,
+    Uint,
+}
+
+/// A user-defined input variable, such as a vertex attribute.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderInput {
+    pub location: u32,
+    pub name: Option<String>,
+    pub numeric_type: NumericType,
+    /// The width of each component in bits.
+    pub bit_width: u32,
+    /// The number of components at each location, e.g. 3 for a `vec3` or a `mat3`.
+    pub component_count: u32,
+    /// The number of consecutive locations used, e.g. 4 for a `mat4`.
+    pub location_count: u32,
+}
+
+impl ShaderInput {
+    /// A GLSL-style name for the type of the input at a single location, such as `vec3` or `uint`.
+    pub fn type_name(&self) -> String {
+        let prefix = match (self.numeric_type, self.bit_width) {
+            (NumericType::Float, 64) => "d",
+            (NumericType::Float, _) => "",
+            (NumericType::Sint, _) => "i",
+            (NumericType::Uint, _) => "u",
+        };
+
+        match (self.component_count, self.numeric_type, self.bit_width) {
+            (1, NumericType::Float, 64) => "double".into(),
+            (1, NumericType::Float, _) => "float".into(),
+            (1, NumericType::Sint, _) => "int".into(),
+            (1, NumericType::Uint, _) => "uint".into(),
+            (count, ..) => format!("{}vec{}", prefix, count),
+        }
+    }
+}
+
+/// The reflected resource interface of a single shader entry point.
+#[derive(Clone, Debug)]
+pub struct ShaderReflection {
+    pub entry_point: EntryPoint,
+    pub descriptor_bindings: Vec<DescriptorBinding>,
+    pub push_constants: Option<PushConstantBlock>,
+    /// The vertex attributes of a vertex shader entry point, sorted by location. Empty for
+    /// other stages, whose inputs can be blocks that don't map to attributes.
+    pub inputs: Vec<ShaderInput>,
+}
+
+impl ShaderReflection {
+    /// Reflect the bytes of a SPIR-V module, as given to [`load_shader_module`](crate::load_shader_module).
+    pub fn from_bytes(bytes: &[u8], entry_point: &str) -> anyhow::Result<Self> {
+        let spv = crate::read_spirv(bytes)?;
+        Self::new(&spv, entry_point)
+    }
+
+    /// Reflect a SPIR-V module, as returned by `ash::util::read_spv`.
+    ///
+    /// Only the descriptor bindings and push constants that the entry point statically uses,
+    /// directly or through function calls, are included. This matters for modules with several
+    /// entry points.
+    pub fn new(spv: &[u32], entry_point: &str) -> anyhow::Result<Self> {
+        let module = Module::parse(spv)?;
+        Self::from_module(&module, entry_point)
+    }
+
+    pub(crate) fn from_module(module: &Module, entry_point: &str) -> anyhow::Result<Self> {
+        let entry_point = module
+            .entry_points
+            .iter()
+            .find(|candidate| candidate.name == entry_point)
+            .cloned()
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Entry point {:?} not found. Available entry points: {:?}",
+                    entry_point,
+                    module
+                        .entry_points
+                        .iter()
+                        .map(|entry_point| &entry_point.name)
+                        .collect::<Vec<_>>()
+                )
+            })?;
+
+        let stages = entry_point.stage;
+        let used_globals = module.used_globals(&entry_point);
+
+        let mut descriptor_bindings = Vec::new();
+        let mut push_constants = None;
+        let mut inputs = Vec::new();
+
+        for &(pointer_type, id, storage_class) in &module.variables {
+            let pointee = match module.ty(pointer_type)? {
+                Type::Pointer { pointee, .. } => *pointee,
+                other => {
+                    return Err(anyhow::anyhow!(
+                        "Variable {} has a non-pointer type {:?}",
+                        id,
+                        other
+                    ))
+                }
+            };
+
+            let name = module.names.get(&id).cloned();
+
+            if !used_globals.contains(&id) {
+                continue;
+            }
+
+            if storage_class == storage_class::PUSH_CONSTANT {
+                let (offset, size) = push_constant_range(module, pointee)?;
+
+                push_constants = Some(PushConstantBlock {
+                    offset,
+                    size,
+                    stages,
+                    name,
+                });
+
+                continue;
+            }
+
+            if storage_class == storage_class::INPUT {
+                let location = module.decorations.get(&id).and_then(|d| d.location);
+
+                // Built-in inputs don't have a location.
+                if let Some(location) = location {
+                    if stages == vk::ShaderStageFlags::VERTEX {
+                        inputs.push(module.shader_input(pointee, location, name)?);
+                    }
+                }
+
+                continue;
+            }
+
+            let (element, count) = match module.ty(pointee)? {
+                Type::Array { element, length } => (*element, *length),
+                Type::RuntimeArray { element } => (*element, 0),
+                _ => (pointee, 1),
+            };
+
+            let descriptor_type = match module.descriptor_type(storage_class, element)? {
+                Some(descriptor_type) => descriptor_type,
+                None => continue,
+            };
+
+            let decorations = module.decorations.get(&id);
+
+            let (set, binding) = match (
+                decorations.and_then(|d| d.descriptor_set),
+                decorations.and_then(|d| d.binding),
+            ) {
+                (Some(set), Some(binding)) => (set, binding),
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Descriptor {:?} is missing a set or binding decoration",
+                        name.unwrap_or_else(|| id.to_string())
+                    ))
+                }
+            };
+
+            descriptor_bindings.push(DescriptorBinding {
+                set,
+                binding,
+                descriptor_type,
+                count,
+                stages,
+                name,
+            });
+        }
+
+        descriptor_bindings.sort_by_key(|binding| (binding.set, binding.binding));
+        inputs.sort_by_key(|input| input.location);
+
+        Ok(Self {
+            entry_point,
+            descriptor_bindings,
+            push_constants,
+            inputs,
+        })
+    }
+}
+
+fn push_constant_range(module: &Module, struct_id: u32) -> anyhow::Result<(u32, u32)> {
+    let members = match module.ty(struct_id)? {
+        Type::Struct { members } => members,
+        other => {
+            return Err(anyhow::anyhow!(
+                "Push constant block has a non-struct type {:?}",
+                other
+            ))
+        }
+    };
+
+    let offset = (0..members.len() as u32)
+        .filter_map(|i| {
+            module
+                .member_decorations
+                .get(&(struct_id, i))
+                .and_then(|d| d.offset)
+        })
+        .min()
+        .unwrap_or(0);
+
+    let size = module.size_of(struct_id, None)?;
+
+    Ok((offset, size - offset))
+}
+
+/// Descriptor set layouts and push constant ranges merged from every stage of a pipeline.
+#[derive(Clone, Debug, Default)]
+pub struct ReflectedPipelineLayout {
+    /// Bindings keyed by set index, sorted by binding.
+    pub sets: BTreeMap<u32, Vec<DescriptorBinding>>,
+    /// A single range covering the push constants of every stage, if any stage uses them.
+    pub push_constant_range: Option<vk::PushConstantRange>,
+}
+
+impl ReflectedPipelineLayout {
+    /// Merge the reflections of every stage in a pipeline. Fails if two stages declare the same
+    /// binding with different descriptor types.
+    pub fn merge(stages: &[&ShaderReflection]) -> anyhow::Result<Self> {
+        let mut layout = Self::default();
+
+        for stage in stages {
+            for binding in &stage.descriptor_bindings {
+                let set = layout.sets.entry(binding.set).or_default();
+
+                match set
+                    .iter_mut()
+                    .find(|other| other.binding == binding.binding)
+                {
+                    Some(existing) => {
+                        if existing.descriptor_type != binding.descriptor_type {
+                            return Err(anyhow::anyhow!(
+                                "Set {} binding {} is a {:?} in {:?} but a {:?} in {:?}",
+                                binding.set,
+                                binding.binding,
+                                existing.descriptor_type,
+                                existing.stages,
+                                binding.descriptor_type,
+                                binding.stages
+                            ));
+                        }
+
+                        existing.stages |= binding.stages;
+
+                        if existing.count != 0 && binding.count != 0 {
+                            existing.count = existing.count.max(binding.count);
+                        } else {
+                            existing.count = 0;
+                        }
+
+                        if existing.name.is_none() {
+                            existing.name = binding.name.clone();
+                        }
+                    }
+                    None => set.push(binding.clone()),
+                }
+            }
+
+            if let Some(push_constants) = &stage.push_constants {
+                let range = layout
+                    .push_constant_range
+                    .get_or_insert(vk::PushConstantRange {
+                        stage_flags: vk::ShaderStageFlags::empty(),
+                        offset: push_constants.offset,
+                        size: 0,
+                    });
+
+                let end =
+                    (range.offset + range.size).max(push_constants.offset + push_constants.size);
+
+                range.offset = range.offset.min(push_constants.offset);
+                range.size = end - range.offset;
+                range.stage_flags |= push_constants.stages;
+            }
+        }
+
+        for set in layout.sets.values_mut() {
+            set.sort_by_key(|binding| binding.binding);
+        }
+
+        Ok(layout)
+    }
+
+    /// The number of set layouts that the pipeline layout needs, including any empty sets
+    /// before the highest set index.
+    pub fn set_count(&self) -> u32 {
+        self.sets.keys().next_back().map_or(0, |&set| set + 1)
+    }
+
+    /// The `vk::DescriptorSetLayoutBinding`s for a set. Runtime-sized arrays are given
+    /// `runtime_array_count` descriptors.
+    pub fn set_layout_bindings(
+        &self,
+        set: u32,
+        runtime_array_count: u32,
+    ) -> Vec<vk::DescriptorSetLayoutBinding> {
+        self.sets
+            .get(&set)
+            .map(|bindings| {
+                bindings
+                    .iter()
+                    .map(|binding| {
+                        *vk::DescriptorSetLayoutBinding::builder()
+                            .binding(binding.binding)
+                            .descriptor_type(binding.descriptor_type)
+                            .descriptor_count(if binding.is_runtime_array() {
+                                runtime_array_count
+                            } else {
+                                binding.count
+                            })
+                            .stage_flags(binding.stages)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn push_constant_ranges(&self) -> &[vk::PushConstantRange] {
+        self.push_constant_range.as_slice()
+    }
+
+    /// Create a descriptor set layout for every set index up to [`Self::set_count`].
+    pub fn create_descriptor_set_layouts(
+        &self,
+        device: &ash::Device,
+        runtime_array_count: u32,
+    ) -> anyhow::Result<Vec<vk::DescriptorSetLayout>> {
+        let mut layouts = Vec::with_capacity(self.set_count() as usize);
+
+        for set in 0..self.set_count() {
+            let bindings = self.set_layout_bindings(set, runtime_array_count);
+
+            let layout = unsafe {
+                device.create_descriptor_set_layout(
+                    &vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings),
+                    None,
+                )
+            };
+
+            match layout {
+                Ok(layout) => layouts.push(layout),
+                Err(error) => {
+                    for layout in layouts {
+                        unsafe { device.destroy_descriptor_set_layout(layout, None) };
+                    }
+
+                    return Err(error.into());
+                }
+            }
+        }
+
+        Ok(layouts)
+    }
+
+    pub fn create_pipeline_layout(
+        &self,
+        device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+    ) -> anyhow::Result<vk::PipelineLayout> {
+        Ok(unsafe {
+            device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::builder()
+                    .set_layouts(set_layouts)
+                    .push_constant_ranges(self.push_constant_ranges()),
+                None,
+            )
+        }?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    const TYPE_FUNCTION: u16 = 33;
+    const FUNCTION_CALL: u16 = 57;
+    const LOAD: u16 = 61;
+    const LABEL: u16 = 248;
+    const RETURN: u16 = 253;
+
+    const EXECUTION_MODEL_VERTEX: u32 = 0;
+    const EXECUTION_MODEL_FRAGMENT: u32 = 4;
+
+    fn string(string: &str) -> Vec<u32> {
+        let mut bytes = string.as_bytes().to_vec();
+        bytes.resize(bytes.len() / 4 * 4 + 4, 0);
+
+        bytes
+            .chunks(4)
+            .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect()
+    }
+
+    fn instruction(words: &mut Vec<u32>, opcode: u16, operands: &[u32]) {
+        words.push((operands.len() as u32 + 1) << 16 | opcode as u32);
+        words.extend_from_slice(operands);
+    }
+
+    /// Assembles just enough of a module for reflection. Functions are kept separate so that
+    /// they end up after every global, as in a real module.
+    struct Builder {
+        version: (u32, u32),
+        globals: Vec<u32>,
+        functions: Vec<u32>,
+        next_id: u32,
+        void: u32,
+        function_type: u32,
+        float: u32,
+        uint: u32,
+        vec4: u32,
+    }
+
+    impl Builder {
+        fn new(version: (u32, u32)) -> Self {
+            let mut builder = Self {
+                version,
+                globals: Vec::new(),
+                functions: Vec::new(),
+                next_id: 1,
+                void: 0,
+                function_type: 0,
+                float: 0,
+                uint: 0,
+                vec4: 0,
+            };
+
+            builder.void = builder.ty(op::TYPE_VOID, &[]);
+            builder.function_type = builder.ty(TYPE_FUNCTION, &[builder.void]);
+            builder.float = builder.ty(op::TYPE_FLOAT, &[32]);
+            builder.uint = builder.ty(op::TYPE_INT, &[32, 0]);
+            builder.vec4 = builder.ty(op::TYPE_VECTOR, &[builder.float, 4]);
+            builder
+        }
+
+        fn id(&mut self) -> u32 {
+            self.next_id += 1;
+            self.next_id - 1
+        }
+
+        fn op(&mut self, opcode: u16, operands: &[u32]) {
+            instruction(&mut self.globals, opcode, operands);
+        }
+
+        fn ty(&mut self, opcode: u16, operands: &[u32]) -> u32 {
+            let id = self.id();
+            self.op(opcode, &[&[id], operands].concat());
+            id
+        }
+
+        fn name(&mut self, id: u32, name: &str) {
+            self.op(op::NAME, &[vec![id], string(name)].concat());
+        }
+
+        fn entry_point(
+            &mut self,
+            execution_model: u32,
+            function: u32,
+            name: &str,
+            interface: &[u32],
+        ) {
+            self.op(
+                op::ENTRY_POINT,
+                &[
+                    vec![execution_model, function],
+                    string(name),
+                    interface.to_vec(),
+                ]
+                .concat(),
+            );
+        }
+
+        fn decorate(&mut self, id: u32, decoration: u32, operands: &[u32]) {
+            self.op(op::DECORATE, &[&[id, decoration], operands].concat());
+        }
+
+        fn member_decorate(&mut self, id: u32, member: u32, decoration: u32, operand: u32) {
+            self.op(op::MEMBER_DECORATE, &[id, member, decoration, operand]);
+        }
+
+        fn array(&mut self, element: u32, length: u32) -> u32 {
+            let constant = self.id();
+            self.op(op::CONSTANT, &[self.uint, constant, length]);
+            self.ty(op::TYPE_ARRAY, &[element, constant])
+        }
+
+        /// A struct with explicit member offsets, decorated with `Block` or `BufferBlock`.
+        fn block(&mut self, members: &[(u32, u32)], decoration: u32) -> u32 {
+            let types: Vec<u32> = members.iter().map(|&(ty, _)| ty).collect();
+            let id = self.ty(op::TYPE_STRUCT, &types);
+
+            self.decorate(id, decoration, &[]);
+
+            for (i, &(_, offset)) in members.iter().enumerate() {
+                self.member_decorate(id, i as u32, decoration::OFFSET, offset);
+            }
+
+            id
+        }
+
+        fn variable(&mut self, pointee: u32, storage_class: u32) -> u32 {
+            let pointer = self.ty(op::TYPE_POINTER, &[storage_class, pointee]);
+            let id = self.id();
+            self.op(op::VARIABLE, &[pointer, id, storage_class]);
+            id
+        }
+
+        fn descriptor(&mut self, pointee: u32, storage_class: u32, set: u32, binding: u32) -> u32 {
+            let id = self.variable(pointee, storage_class);
+            self.decorate(id, decoration::DESCRIPTOR_SET, &[set]);
+            self.decorate(id, decoration::BINDING, &[binding]);
+            id
+        }
+
+        /// A function that loads every variable in `uses` and calls every function in `calls`.
+        fn function(&mut self, id: u32, uses: &[u32], calls: &[u32]) {
+            let mut words = Vec::new();
+
+            instruction(
+                &mut words,
+                op::FUNCTION,
+                &[self.void, id, 0, self.function_type],
+            );
+            instruction(&mut words, LABEL, &[self.id()]);
+
+            for &variable in uses {
+                instruction(&mut words, LOAD, &[self.float, self.id(), variable]);
+            }
+
+            for &function in calls {
+                instruction(&mut words, FUNCTION_CALL, &[self.void, self.id(), function]);
+            }
+
+            instruction(&mut words, RETURN, &[]);
+            instruction(&mut words, op::FUNCTION_END, &[]);
+
+            self.functions.extend(words);
+        }
+
+        fn finish(self) -> Vec<u32> {
+            let (major, minor) = self.version;
+
+            [
+                vec![0x0723_0203, major << 16 | minor << 8, 0, self.next_id, 0],
+                self.globals,
+                self.functions,
+            ]
+            .concat()
+        }
+    }
+
+    /// Reflect a vertex shader with an input at each location, given by its GLSL type, such as
+    /// `vec3`, `uvec2`, `dvec4` or `mat4`.
+    pub(crate) fn vertex_shader(inputs: &[(u32, &str)]) -> ShaderReflection {
+        let mut b = Builder::new((1, 0));
+        let main = b.id();
+        let int = b.ty(op::TYPE_INT, &[32, 1]);
+        let double = b.ty(op::TYPE_FLOAT, &[64]);
+
+        let variables: Vec<u32> = inputs
+            .iter()
+            .map(|&(location, ty)| {
+                let (component, shape) = match ty {
+                    "float" => (b.float, ""),
+                    "int" => (int, ""),
+                    "uint" => (b.uint, ""),
+                    "double" => (double, ""),
+                    _ => match ty.split_at(1) {
+                        ("i", shape) => (int, shape),
+                        ("u", shape) => (b.uint, shape),
+                        ("d", shape) => (double, shape),
+                        _ => (b.float, ty),
+                    },
+                };
+
+                let ty = match shape.split_at(shape.len().saturating_sub(1)) {
+                    ("", _) => component,
+                    ("vec", count) => b.ty(op::TYPE_VECTOR, &[component, count.parse().unwrap()]),
+                    ("mat", count) => {
+                        let count = count.parse().unwrap();
+                        let column = b.ty(op::TYPE_VECTOR, &[component, count]);
+                        b.ty(op::TYPE_MATRIX, &[column, count])
+                    }
+                    _ => panic!("Unknown type {:?}", ty),
+                };
+
+                let variable = b.variable(ty, storage_class::INPUT);
+                b.decorate(variable, decoration::LOCATION, &[location]);
+                variable
+            })
+            .collect();
+
+        b.entry_point(EXECUTION_MODEL_VERTEX, main, "main", &variables);
+        b.function(main, &[], &[]);
+
+        ShaderReflection::new(&b.finish(), "main").unwrap()
+    }
+
+    fn bindings(reflection: &ShaderReflection) -> Vec<(u32, u32, vk::DescriptorType, u32)> {
+        reflection
+            .descriptor_bindings
+            .iter()
+            .map(|binding| {
+                (
+                    binding.set,
+                    binding.binding,
+                    binding.descriptor_type,
+                    binding.count,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn maps_descriptor_types_and_counts() {
+        let mut b = Builder::new((1, 0));
+        let main = b.id();
+
+        let image = |b: &mut Builder, dim, sampled| {
+            b.ty(op::TYPE_IMAGE, &[b.float, dim, 0, 0, 0, sampled, 0])
+        };
+
+        let sampled_image_2d = image(&mut b, 1, 1);
+        let storage_image_2d = image(&mut b, 1, 2);
+        let uniform_texel_buffer = image(&mut b, DIM_BUFFER, 1);
+        let storage_texel_buffer = image(&mut b, DIM_BUFFER, 2);
+        let subpass_data = image(&mut b, DIM_SUBPASS_DATA, 2);
+        let sampler = b.ty(op::TYPE_SAMPLER, &[]);
+        let combined = b.ty(op::TYPE_SAMPLED_IMAGE, &[sampled_image_2d]);
+        let image_array = b.array(sampled_image_2d, 4);
+        let runtime_array = b.ty(op::TYPE_RUNTIME_ARRAY, &[combined]);
+        let uniform_block = b.block(&[(b.vec4, 0)], decoration::BLOCK);
+        let buffer_block = b.block(&[(b.vec4, 0)], decoration::BUFFER_BLOCK);
+        let storage_block = b.block(&[(b.vec4, 0)], decoration::BLOCK);
+
+        use storage_class::*;
+
+        let variables = [
+            b.descriptor(sampler, UNIFORM_CONSTANT, 0, 0),
+            b.descriptor(combined, UNIFORM_CONSTANT, 0, 1),
+            b.descriptor(sampled_image_2d, UNIFORM_CONSTANT, 0, 2),
+            b.descriptor(storage_image_2d, UNIFORM_CONSTANT, 0, 3),
+            b.descriptor(uniform_texel_buffer, UNIFORM_CONSTANT, 0, 4),
+            b.descriptor(storage_texel_buffer, UNIFORM_CONSTANT, 0, 5),
+            b.descriptor(subpass_data, UNIFORM_CONSTANT, 0, 6),
+            b.descriptor(uniform_block, UNIFORM, 1, 0),
+            b.descriptor(buffer_block, UNIFORM, 1, 1),
+            b.descriptor(storage_block, STORAGE_BUFFER, 1, 2),
+            b.descriptor(image_array, UNIFORM_CONSTANT, 2, 1),
+            b.descriptor(runtime_array, UNIFORM_CONSTANT, 2, 0),
+        ];
+
+        b.name(variables[7], "camera");
+        b.entry_point(EXECUTION_MODEL_FRAGMENT, main, "main", &[]);
+        b.function(main, &variables, &[]);
+
+        let reflection = ShaderReflection::new(&b.finish(), "main").unwrap();
+
+        use vk::DescriptorType as D;
+
+        assert_eq!(
+            bindings(&reflection),
+            [
+                (0, 0, D::SAMPLER, 1),
+                (0, 1, D::COMBINED_IMAGE_SAMPLER, 1),
+                (0, 2, D::SAMPLED_IMAGE, 1),
+                (0, 3, D::STORAGE_IMAGE, 1),
+                (0, 4, D::UNIFORM_TEXEL_BUFFER, 1),
+                (0, 5, D::STORAGE_TEXEL_BUFFER, 1),
+                (0, 6, D::INPUT_ATTACHMENT, 1),
+                (1, 0, D::UNIFORM_BUFFER, 1),
+                (1, 1, D::STORAGE_BUFFER, 1),
+                (1, 2, D::STORAGE_BUFFER, 1),
+                (2, 0, D::COMBINED_IMAGE_SAMPLER, 0),
+                (2, 1, D::SAMPLED_IMAGE, 4),
+            ]
+        );
+
+        assert_eq!(
+            reflection.descriptor_bindings[7].name.as_deref(),
+            Some("camera")
+        );
+        assert!(reflection.descriptor_bindings[10].is_runtime_array());
+        assert!(reflection
+            .descriptor_bindings
+            .iter()
+            .all(|binding| binding.stages == vk::ShaderStageFlags::FRAGMENT));
+    }
+
+    #[test]
+    fn measures_push_constant_blocks() {
+        let mut b = Builder::new((1, 0));
+        let main = b.id();
+
+        let mat4 = b.ty(op::TYPE_MATRIX, &[b.vec4, 4]);
+        let block = b.block(
+            &[(b.float, 16), (b.vec4, 32), (mat4, 48)],
+            decoration::BLOCK,
+        );
+        b.member_decorate(block, 2, decoration::MATRIX_STRIDE, 16);
+
+        let push_constants = b.variable(block, storage_class::PUSH_CONSTANT);
+        b.name(push_constants, "constants");
+
+        b.entry_point(EXECUTION_MODEL_VERTEX, main, "main", &[]);
+        b.function(main, &[push_constants], &[]);
+
+        let reflection = ShaderReflection::new(&b.finish(), "main").unwrap();
+
+        assert_eq!(
+            reflection.push_constants,
+            Some(PushConstantBlock {
+                offset: 16,
+                size: 48 + 64 - 16,
+                stages: vk::ShaderStageFlags::VERTEX,
+                name: Some("constants".to_string()),
+            })
+        );
+    }
+
+    /// A vertex and a fragment entry point that use different resources, where the vertex
+    /// shader's use is through a function call. `interface` decides whether the globals are
+    /// listed in the entry points, as SPIR-V 1.4 requires.
+    fn multiple_entry_points(version: (u32, u32), interface: bool) -> Vec<u32> {
+        let mut b = Builder::new(version);
+        let vs = b.id();
+        let fs = b.id();
+        let helper = b.id();
+
+        let uniform_block = b.block(&[(b.vec4, 0)], decoration::BLOCK);
+        let image = b.ty(op::TYPE_IMAGE, &[b.float, 1, 0, 0, 0, 1, 0]);
+        let combined = b.ty(op::TYPE_SAMPLED_IMAGE, &[image]);
+        let push_block = b.block(&[(b.vec4, 0)], decoration::BLOCK);
+
+        let uniforms = b.descriptor(uniform_block, storage_class::UNIFORM, 0, 0);
+        let texture = b.descriptor(combined, storage_class::UNIFORM_CONSTANT, 0, 1);
+        let push_constants = b.variable(push_block, storage_class::PUSH_CONSTANT);
+        // Not used by either entry point.
+        b.descriptor(combined, storage_class::UNIFORM_CONSTANT, 0, 2);
+
+        if interface {
+            b.entry_point(EXECUTION_MODEL_VERTEX, vs, "vs", &[uniforms]);
+            b.entry_point(
+                EXECUTION_MODEL_FRAGMENT,
+                fs,
+                "fs",
+                &[texture, push_constants],
+            );
+            b.function(vs, &[], &[helper]);
+            b.function(fs, &[], &[]);
+            b.function(helper, &[], &[]);
+        } else {
+            b.entry_point(EXECUTION_MODEL_VERTEX, vs, "vs", &[]);
+            b.entry_point(EXECUTION_MODEL_FRAGMENT, fs, "fs", &[]);
+            b.function(vs, &[], &[helper]);
+            b.function(fs, &[texture, push_constants], &[]);
+            b.function(helper, &[uniforms], &[]);
+        }
+
+        b.finish()
+    }
+
+    fn check_multiple_entry_points(spv: &[u32]) {
+        let vs = ShaderReflection::new(spv, "vs").unwrap();
+        let fs = ShaderReflection::new(spv, "fs").unwrap();
+
+        assert_eq!(
+            bindings(&vs),
+            [(0, 0, vk::DescriptorType::UNIFORM_BUFFER, 1)]
+        );
+        assert_eq!(vs.push_constants, None);
+
+        assert_eq!(
+            bindings(&fs),
+            [(0, 1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1)]
+        );
+        assert_eq!(
+            fs.push_constants.as_ref().map(|block| block.stages),
+            Some(vk::ShaderStageFlags::FRAGMENT)
+        );
+
+        let layout = ReflectedPipelineLayout::merge(&[&vs, &fs]).unwrap();
+        let stages: Vec<_> = layout.sets[&0]
+            .iter()
+            .map(|binding| binding.stages)
+            .collect();
+
+        assert_eq!(
+            stages,
+            [vk::ShaderStageFlags::VERTEX, vk::ShaderStageFlags::FRAGMENT]
+        );
+    }
+
+    #[test]
+    fn follows_function_calls_before_spirv_1_4() {
+        check_multiple_entry_points(&multiple_entry_points((1, 0), false));
+    }
+
+    #[test]
+    fn uses_the_interface_from_spirv_1_4() {
+        check_multiple_entry_points(&multiple_entry_points((1, 4), true));
+    }
+
+    #[test]
+    fn reports_missing_entry_points_and_decorations() {
+        let mut b = Builder::new((1, 0));
+        let main = b.id();
+        let sampler = b.ty(op::TYPE_SAMPLER, &[]);
+        let variable = b.variable(sampler, storage_class::UNIFORM_CONSTANT);
+        b.decorate(variable, decoration::BINDING, &[0]);
+        b.name(variable, "lonely_sampler");
+        b.entry_point(EXECUTION_MODEL_FRAGMENT, main, "main", &[]);
+        b.function(main, &[variable], &[]);
+        let spv = b.finish();
+
+        let error = ShaderReflection::new(&spv, "vs_main").unwrap_err();
+        assert!(error.to_string().contains("[\"main\"]"), "{}", error);
+
+        let error = ShaderReflection::new(&spv, "main").unwrap_err();
+        assert!(error.to_string().contains("lonely_sampler"), "{}", error);
+
+        assert!(ShaderReflection::new(&spv[..3], "main").is_err());
+    }
+
+    #[test]
+    fn reflects_vertex_inputs() {
+        let mut b = Builder::new((1, 0));
+        let main = b.id();
+
+        let vec3 = b.ty(op::TYPE_VECTOR, &[b.float, 3]);
+        let mat4 = b.ty(op::TYPE_MATRIX, &[b.vec4, 4]);
+        let int = b.ty(op::TYPE_INT, &[32, 1]);
+        let ivec2 = b.ty(op::TYPE_VECTOR, &[int, 2]);
+
+        let position = b.variable(vec3, storage_class::INPUT);
+        let transform = b.variable(mat4, storage_class::INPUT);
+        let ids = b.variable(ivec2, storage_class::INPUT);
+        let vertex_index = b.variable(b.uint, storage_class::INPUT);
+
+        b.decorate(position, decoration::LOCATION, &[0]);
+        b.decorate(transform, decoration::LOCATION, &[1]);
+        b.decorate(ids, decoration::LOCATION, &[5]);
+        b.name(position, "position");
+
+        b.entry_point(
+            EXECUTION_MODEL_VERTEX,
+            main,
+            "main",
+            &[ids, position, transform, vertex_index],
+        );
+        b.function(main, &[], &[]);
+
+        let reflection = ShaderReflection::new(&b.finish(), "main").unwrap();
+
+        let inputs: Vec<_> = reflection
+            .inputs
+            .iter()
+            .map(|input| {
+                (
+                    input.location,
+                    input.type_name(),
+                    input.component_count,
+                    input.location_count,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            inputs,
+            [
+                (0, "vec3".to_string(), 3, 1),
+                (1, "vec4".to_string(), 4, 4),
+                (5, "ivec2".to_string(), 2, 1),
+            ]
+        );
+        assert_eq!(reflection.inputs[0].name.as_deref(), Some("position"));
+    }
+
+    #[test]
+    fn ignores_block_inputs_of_other_stages() {
+        // `layout(location = 0) in Block { vec3 normal; } block;` and a uniform buffer in a
+        // fragment shader.
+        let mut b = Builder::new((1, 0));
+        let main = b.id();
+
+        let vec3 = b.ty(op::TYPE_VECTOR, &[b.float, 3]);
+        let input_block = b.ty(op::TYPE_STRUCT, &[vec3]);
+        b.decorate(input_block, decoration::BLOCK, &[]);
+        let uniform_block = b.block(&[(b.vec4, 0)], decoration::BLOCK);
+
+        let input = b.variable(input_block, storage_class::INPUT);
+        b.decorate(input, decoration::LOCATION, &[0]);
+        let uniforms = b.descriptor(uniform_block, storage_class::UNIFORM, 0, 0);
+
+        b.entry_point(EXECUTION_MODEL_FRAGMENT, main, "main", &[input]);
+        b.function(main, &[input, uniforms], &[]);
+
+        let reflection = ShaderReflection::new(&b.finish(), "main").unwrap();
+
+        assert!(reflection.inputs.is_empty());
+        assert_eq!(
+            bindings(&reflection),
+            [(0, 0, vk::DescriptorType::UNIFORM_BUFFER, 1)]
+        );
+    }
+
+    fn reflection(
+        stage: vk::ShaderStageFlags,
+        bindings: &[(u32, u32, vk::DescriptorType, u32)],
+        push_constants: Option<(u32, u32)>,
+    ) -> ShaderReflection {
+        ShaderReflection {
+            entry_point: EntryPoint {
+                name: "main".to_string(),
+                stage,
+                function: 0,
+                interface: Vec::new(),
+            },
+            descriptor_bindings: bindings
+                .iter()
+                .map(
+                    |&(set, binding, descriptor_type, count)| DescriptorBinding {
+                        set,
+                        binding,
+                        descriptor_type,
+                        count,
+                        stages: stage,
+                        name: None,
+                    },
+                )
+                .collect(),
+            push_constants: push_constants.map(|(offset, size)| PushConstantBlock {
+                offset,
+                size,
+                stages: stage,
+                name: None,
+            }),
+            inputs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn merges_stages() {
+        use vk::DescriptorType as D;
+        use vk::ShaderStageFlags as S;
+
+        let vertex = reflection(
+            S::VERTEX,
+            &[(0, 0, D::UNIFORM_BUFFER, 1), (0, 2, D::SAMPLED_IMAGE, 2)],
+            Some((0, 64)),
+        );
+        let fragment = reflection(
+            S::FRAGMENT,
+            &[
+                (0, 1, D::COMBINED_IMAGE_SAMPLER, 0),
+                (0, 0, D::UNIFORM_BUFFER, 1),
+                (0, 2, D::SAMPLED_IMAGE, 4),
+                (2, 0, D::STORAGE_BUFFER, 1),
+            ],
+            Some((64, 16)),
+        );
+
+        let layout = ReflectedPipelineLayout::merge(&[&vertex, &fragment]).unwrap();
+
+        assert_eq!(layout.set_count(), 3);
+
+        let set_0: Vec<_> = layout
+            .set_layout_bindings(0, 8)
+            .iter()
+            .map(|binding| {
+                (
+                    binding.binding,
+                    binding.descriptor_type,
+                    binding.descriptor_count,
+                    binding.stage_flags,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            set_0,
+            [
+                (0, D::UNIFORM_BUFFER, 1, S::VERTEX | S::FRAGMENT),
+                (1, D::COMBINED_IMAGE_SAMPLER, 8, S::FRAGMENT),
+                (2, D::SAMPLED_IMAGE, 4, S::VERTEX | S::FRAGMENT),
+            ]
+        );
+
+        assert!(layout.set_layout_bindings(1, 8).is_empty());
+        assert_eq!(layout.set_layout_bindings(2, 8).len(), 1);
+
+        let range = layout.push_constant_range.unwrap();
+
+        assert_eq!(range.stage_flags, S::VERTEX | S::FRAGMENT);
+        assert_eq!((range.offset, range.size), (0, 80));
+    }
+
+    #[test]
+    fn merging_a_runtime_array_keeps_it_runtime_sized() {
+        use vk::DescriptorType as D;
+
+        let vertex = reflection(
+            vk::ShaderStageFlags::VERTEX,
+            &[(0, 0, D::SAMPLED_IMAGE, 4)],
+            None,
+        );
+        let fragment = reflection(
+            vk::ShaderStageFlags::FRAGMENT,
+            &[(0, 0, D::SAMPLED_IMAGE, 0)],
+            None,
+        );
+
+        let layout = ReflectedPipelineLayout::merge(&[&vertex, &fragment]).unwrap();
+
+        assert!(layout.sets[&0][0].is_runtime_array());
+        assert!(layout.push_constant_ranges().is_empty());
+    }
+
+    #[test]
+    fn merging_conflicting_types_fails() {
+        use vk::DescriptorType as D;
+
+        let vertex = reflection(
+            vk::ShaderStageFlags::VERTEX,
+            &[(1, 3, D::UNIFORM_BUFFER, 1)],
+            None,
+        );
+        let fragment = reflection(
+            vk::ShaderStageFlags::FRAGMENT,
+            &[(1, 3, D::STORAGE_BUFFER, 1)],
+            None,
+        );
+
+        let error = ReflectedPipelineLayout::merge(&[&vertex, &fragment]).unwrap_err();
+
+        assert!(error.to_string().contains("Set 1 binding 3"), "{}", error);
+    }
+}
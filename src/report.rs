@@ -0,0 +1,229 @@
+use crate::requirements::format_api_version;
+use ash::vk;
+use std::fmt;
+
+/// A record of every device that was considered during physical device selection, and why it
+/// was or wasn't chosen.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeviceSelectionReport {
+    pub devices: Vec<DeviceReport>,
+    /// The index into `devices` of the selected device, if any.
+    pub selected: Option<usize>,
+}
+
+impl DeviceSelectionReport {
+    pub fn selected_device(&self) -> Option<&DeviceReport> {
+        self.selected.map(|index| &self.devices[index])
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeviceReport {
+    /// The index of the device in `vkEnumeratePhysicalDevices`.
+    pub index: usize,
+    pub name: String,
+    pub device_type: String,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub api_version: String,
+    pub driver_version: u32,
+    pub pipeline_cache_uuid: String,
+    pub supported_optional_extensions: Vec<String>,
+    /// Every reason that the device was rejected. Empty if the device is eligible.
+    pub rejections: Vec<RejectionReason>,
+    /// The score of the device if it was eligible. The highest scoring device is selected.
+    pub score: Option<u64>,
+}
+
+impl DeviceReport {
+    pub(crate) fn new(index: usize, properties: &vk::PhysicalDeviceProperties) -> Self {
+        Self {
+            index,
+            name: unsafe { crate::cstr_from_array(&properties.device_name) }
+                .to_string_lossy()
+                .into_owned(),
+            device_type: format!("{:?}", properties.device_type),
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            api_version: format_api_version(properties.api_version),
+            driver_version: properties.driver_version,
+            pipeline_cache_uuid: properties
+                .pipeline_cache_uuid
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+            supported_optional_extensions: Vec::new(),
+            rejections: Vec::new(),
+            score: None,
+        }
+    }
+
+    pub fn is_eligible(&self) -> bool {
+        self.rejections.is_empty()
+    }
+}
+
+/// A reason that a device was rejected during selection.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum RejectionReason {
+    /// A device override was set and this device didn't match it.
+    ExcludedByOverride,
+    NoGraphicsQueueFamily,
+    /// Only used for headless selection.
+    NoComputeQueueFamily,
+    NoSurfaceFormat,
+    MissingExtension(String),
+    ApiVersionTooLow {
+        required: String,
+        supported: String,
+    },
+    MissingFeature(String),
+    /// Vulkan 1.1 or 1.2 features were requested, but the instance was created with an older
+    /// api version, so they can't be queried.
+    InstanceApiVersionTooLow {
+        required: String,
+        supported: String,
+    },
+    LimitTooLow {
+        limit: String,
+        required: f64,
+        supported: f64,
+    },
+    /// A Vulkan call made while checking the device failed.
+    VulkanError {
+        call: String,
+        result: String,
+    },
+}
+
+impl fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ExcludedByOverride => write!(f, "The device doesn't match the device override"),
+            Self::NoGraphicsQueueFamily => {
+                write!(f, "No queue family supports graphics and presentation")
+            }
+            Self::NoComputeQueueFamily => {
+                write!(f, "No queue family supports graphics or compute")
+            }
+            Self::NoSurfaceFormat => write!(f, "No surface formats are available"),
+            Self::MissingExtension(extension) => {
+                write!(f, "The {} extension is not supported", extension)
+            }
+            Self::ApiVersionTooLow {
+                required,
+                supported,
+            } => write!(
+                f,
+                "Vulkan {} is required but only {} is supported",
+                required, supported
+            ),
+            Self::MissingFeature(feature) => {
+                write!(f, "The {} feature is not supported", feature)
+            }
+            Self::InstanceApiVersionTooLow {
+                required,
+                supported,
+            } => write!(
+                f,
+                "The instance must be created with Vulkan {} to query the requested features, but \
+                 was created with {}",
+                required, supported
+            ),
+            Self::LimitTooLow {
+                limit,
+                required,
+                supported,
+            } => write!(
+                f,
+                "{} must be at least {} but is {}",
+                limit, required, supported
+            ),
+            Self::VulkanError { call, result } => write!(f, "{} failed with {}", call, result),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_to_json() {
+        let mut rejected = DeviceReport::new(0, &vk::PhysicalDeviceProperties::default());
+        rejected.rejections = vec![
+            RejectionReason::ExcludedByOverride,
+            RejectionReason::NoGraphicsQueueFamily,
+            RejectionReason::NoComputeQueueFamily,
+            RejectionReason::NoSurfaceFormat,
+            RejectionReason::MissingExtension("VK_KHR_swapchain".to_string()),
+            RejectionReason::ApiVersionTooLow {
+                required: "1.2.0".to_string(),
+                supported: "1.1.0".to_string(),
+            },
+            RejectionReason::MissingFeature("sampler_anisotropy".to_string()),
+            RejectionReason::InstanceApiVersionTooLow {
+                required: "1.2.0".to_string(),
+                supported: "1.0.0".to_string(),
+            },
+            RejectionReason::LimitTooLow {
+                limit: "max_push_constants_size".to_string(),
+                required: 256.0,
+                supported: 128.0,
+            },
+            RejectionReason::VulkanError {
+                call: "vkEnumerateDeviceExtensionProperties".to_string(),
+                result: "ERROR_OUT_OF_HOST_MEMORY".to_string(),
+            },
+        ];
+
+        let mut selected = DeviceReport::new(1, &vk::PhysicalDeviceProperties::default());
+        selected.supported_optional_extensions = vec!["VK_EXT_memory_budget".to_string()];
+        selected.score = Some(42);
+
+        let report = DeviceSelectionReport {
+            devices: vec![rejected, selected],
+            selected: Some(1),
+        };
+
+        let json = serde_json::to_value(&report).unwrap();
+
+        assert_eq!(json["selected"], 1);
+        assert_eq!(json["devices"][1]["score"], 42);
+        assert_eq!(json["devices"][1]["api_version"], "0.0.0");
+        assert_eq!(
+            json["devices"][1]["supported_optional_extensions"],
+            serde_json::json!(["VK_EXT_memory_budget"])
+        );
+        assert!(json["devices"][0]["score"].is_null());
+        assert_eq!(
+            json["devices"][0]["rejections"],
+            serde_json::json!([
+                "ExcludedByOverride",
+                "NoGraphicsQueueFamily",
+                "NoComputeQueueFamily",
+                "NoSurfaceFormat",
+                { "MissingExtension": "VK_KHR_swapchain" },
+                { "ApiVersionTooLow": { "required": "1.2.0", "supported": "1.1.0" } },
+                { "MissingFeature": "sampler_anisotropy" },
+                { "InstanceApiVersionTooLow": { "required": "1.2.0", "supported": "1.0.0" } },
+                {
+                    "LimitTooLow": {
+                        "limit": "max_push_constants_size",
+                        "required": 256.0,
+                        "supported": 128.0
+                    }
+                },
+                {
+                    "VulkanError": {
+                        "call": "vkEnumerateDeviceExtensionProperties",
+                        "result": "ERROR_OUT_OF_HOST_MEMORY"
+                    }
+                },
+            ])
+        );
+    }
+}
@@ -0,0 +1,459 @@
+use crate::{tick, RejectionReason};
+use ash::vk;
+
+/// Requirements that a physical device must meet in order to be selected.
+///
+/// Everything defaults to zero/false, which means 'no requirement'.
+#[derive(Clone, Copy, Default)]
+pub struct DeviceRequirements {
+    /// The minimum Vulkan API version, e.g. `vk::API_VERSION_1_2`.
+    pub api_version: u32,
+    pub features: vk::PhysicalDeviceFeatures,
+    /// Requesting any of these requires both the instance and the device to support Vulkan 1.2.
+    pub vulkan_1_1_features: vk::PhysicalDeviceVulkan11Features,
+    /// Requesting any of these requires both the instance and the device to support Vulkan 1.2.
+    pub vulkan_1_2_features: vk::PhysicalDeviceVulkan12Features,
+    pub limits: LimitRequirements,
+}
+
+/// Minimum values for a subset of `vk::PhysicalDeviceLimits`.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct LimitRequirements {
+    pub max_image_dimension_2d: u32,
+    pub max_push_constants_size: u32,
+    pub max_bound_descriptor_sets: u32,
+    pub max_per_stage_descriptor_sampled_images: u32,
+    pub max_per_stage_descriptor_storage_buffers: u32,
+    pub max_descriptor_set_sampled_images: u32,
+    pub max_descriptor_set_storage_buffers: u32,
+    pub max_compute_work_group_invocations: u32,
+    pub max_sampler_anisotropy: f32,
+}
+
+/// Generates a function that returns each requested feature along with whether it's supported.
+macro_rules! feature_lists {
+    ($($name:ident: $ty:ty => [$($field:ident),* $(,)?];)*) => {
+        $(
+            fn $name(required: &$ty, supported: &$ty) -> Vec<(&'static str, bool)> {
+                let mut features = Vec::new();
+                $(
+                    if required.$field == vk::TRUE {
+                        features.push((stringify!($field), supported.$field == vk::TRUE));
+                    }
+                )*
+                features
+            }
+        )*
+    };
+}
+
+feature_lists! {
+    check_core_features: vk::PhysicalDeviceFeatures => [
+        robust_buffer_access,
+        full_draw_index_uint32,
+        image_cube_array,
+        independent_blend,
+        geometry_shader,
+        tessellation_shader,
+        sample_rate_shading,
+        dual_src_blend,
+        logic_op,
+        multi_draw_indirect,
+        draw_indirect_first_instance,
+        depth_clamp,
+        depth_bias_clamp,
+        fill_mode_non_solid,
+        depth_bounds,
+        wide_lines,
+        large_points,
+        alpha_to_one,
+        multi_viewport,
+        sampler_anisotropy,
+        texture_compression_etc2,
+        texture_compression_astc_ldr,
+        texture_compression_bc,
+        occlusion_query_precise,
+        pipeline_statistics_query,
+        vertex_pipeline_stores_and_atomics,
+        fragment_stores_and_atomics,
+        shader_tessellation_and_geometry_point_size,
+        shader_image_gather_extended,
+        shader_storage_image_extended_formats,
+        shader_storage_image_multisample,
+        shader_storage_image_read_without_format,
+        shader_storage_image_write_without_format,
+        shader_uniform_buffer_array_dynamic_indexing,
+        shader_sampled_image_array_dynamic_indexing,
+        shader_storage_buffer_array_dynamic_indexing,
+        shader_storage_image_array_dynamic_indexing,
+        shader_clip_distance,
+        shader_cull_distance,
+        shader_float64,
+        shader_int64,
+        shader_int16,
+        shader_resource_residency,
+        shader_resource_min_lod,
+        sparse_binding,
+        sparse_residency_buffer,
+        sparse_residency_image2_d,
+        sparse_residency_image3_d,
+        sparse_residency2_samples,
+        sparse_residency4_samples,
+        sparse_residency8_samples,
+        sparse_residency16_samples,
+        sparse_residency_aliased,
+        variable_multisample_rate,
+        inherited_queries,
+    ];
+    check_vulkan_1_1_features: vk::PhysicalDeviceVulkan11Features => [
+        storage_buffer16_bit_access,
+        uniform_and_storage_buffer16_bit_access,
+        storage_push_constant16,
+        storage_input_output16,
+        multiview,
+        multiview_geometry_shader,
+        multiview_tessellation_shader,
+        variable_pointers_storage_buffer,
+        variable_pointers,
+        protected_memory,
+        sampler_ycbcr_conversion,
+        shader_draw_parameters,
+    ];
+    check_vulkan_1_2_features: vk::PhysicalDeviceVulkan12Features => [
+        sampler_mirror_clamp_to_edge,
+        draw_indirect_count,
+        storage_buffer8_bit_access,
+        uniform_and_storage_buffer8_bit_access,
+        storage_push_constant8,
+        shader_buffer_int64_atomics,
+        shader_shared_int64_atomics,
+        shader_float16,
+        shader_int8,
+        descriptor_indexing,
+        shader_input_attachment_array_dynamic_indexing,
+        shader_uniform_texel_buffer_array_dynamic_indexing,
+        shader_storage_texel_buffer_array_dynamic_indexing,
+        shader_uniform_buffer_array_non_uniform_indexing,
+        shader_sampled_image_array_non_uniform_indexing,
+        shader_storage_buffer_array_non_uniform_indexing,
+        shader_storage_image_array_non_uniform_indexing,
+        shader_input_attachment_array_non_uniform_indexing,
+        shader_uniform_texel_buffer_array_non_uniform_indexing,
+        shader_storage_texel_buffer_array_non_uniform_indexing,
+        descriptor_binding_uniform_buffer_update_after_bind,
+        descriptor_binding_sampled_image_update_after_bind,
+        descriptor_binding_storage_image_update_after_bind,
+        descriptor_binding_storage_buffer_update_after_bind,
+        descriptor_binding_uniform_texel_buffer_update_after_bind,
+        descriptor_binding_storage_texel_buffer_update_after_bind,
+        descriptor_binding_update_unused_while_pending,
+        descriptor_binding_partially_bound,
+        descriptor_binding_variable_descriptor_count,
+        runtime_descriptor_array,
+        sampler_filter_minmax,
+        scalar_block_layout,
+        imageless_framebuffer,
+        uniform_buffer_standard_layout,
+        shader_subgroup_extended_types,
+        separate_depth_stencil_layouts,
+        host_query_reset,
+        timeline_semaphore,
+        buffer_device_address,
+        buffer_device_address_capture_replay,
+        buffer_device_address_multi_device,
+        vulkan_memory_model,
+        vulkan_memory_model_device_scope,
+        vulkan_memory_model_availability_visibility_chains,
+        shader_output_viewport_index,
+        shader_output_layer,
+        subgroup_broadcast_dynamic_id,
+    ];
+}
+
+impl LimitRequirements {
+    fn check(&self, limits: &vk::PhysicalDeviceLimits, rejections: &mut Vec<RejectionReason>) {
+        let mut logged_header = false;
+
+        macro_rules! check_limits {
+            ($($field:ident => $device_field:ident),* $(,)?) => {
+                $(
+                    if self.$field > Default::default() {
+                        if !logged_header {
+                            log::info!("  Checking device limits:");
+                            logged_header = true;
+                        }
+
+                        let meets_limit = limits.$device_field >= self.$field;
+
+                        log::info!(
+                            "    * {} >= {}: {} (device has {})",
+                            stringify!($field),
+                            self.$field,
+                            tick(meets_limit),
+                            limits.$device_field
+                        );
+
+                        if !meets_limit {
+                            rejections.push(RejectionReason::LimitTooLow {
+                                limit: stringify!($field).to_string(),
+                                required: self.$field as f64,
+                                supported: limits.$device_field as f64,
+                            });
+                        }
+                    }
+                )*
+            };
+        }
+
+        check_limits! {
+            max_image_dimension_2d => max_image_dimension2_d,
+            max_push_constants_size => max_push_constants_size,
+            max_bound_descriptor_sets => max_bound_descriptor_sets,
+            max_per_stage_descriptor_sampled_images => max_per_stage_descriptor_sampled_images,
+            max_per_stage_descriptor_storage_buffers => max_per_stage_descriptor_storage_buffers,
+            max_descriptor_set_sampled_images => max_descriptor_set_sampled_images,
+            max_descriptor_set_storage_buffers => max_descriptor_set_storage_buffers,
+            max_compute_work_group_invocations => max_compute_work_group_invocations,
+            max_sampler_anisotropy => max_sampler_anisotropy,
+        }
+    }
+}
+
+impl DeviceRequirements {
+    pub(crate) fn requests_vulkan_1_2_structs(&self) -> bool {
+        !check_vulkan_1_1_features(&self.vulkan_1_1_features, &self.vulkan_1_1_features).is_empty()
+            || !check_vulkan_1_2_features(&self.vulkan_1_2_features, &self.vulkan_1_2_features)
+                .is_empty()
+    }
+
+    /// `vk::PhysicalDeviceVulkan11Features` and `vk::PhysicalDeviceVulkan12Features` can only be
+    /// chained into `vkGetPhysicalDeviceFeatures2` with a Vulkan 1.2 instance. Returns whether
+    /// they can be queried, recording a rejection if they were requested but can't be.
+    fn check_instance_api_version(
+        &self,
+        instance_api_version: u32,
+        rejections: &mut Vec<RejectionReason>,
+    ) -> bool {
+        if !self.requests_vulkan_1_2_structs() {
+            return false;
+        }
+
+        let meets_api_version = instance_api_version >= vk::API_VERSION_1_2;
+
+        log::info!(
+            "  Checking for an instance api version >= 1.2.0: {} (instance has {})",
+            tick(meets_api_version),
+            format_api_version(instance_api_version)
+        );
+
+        if !meets_api_version {
+            rejections.push(RejectionReason::InstanceApiVersionTooLow {
+                required: format_api_version(vk::API_VERSION_1_2),
+                supported: format_api_version(instance_api_version),
+            });
+        }
+
+        meets_api_version
+    }
+
+    /// Check the requirements against a physical device, logging each one that was requested
+    /// and recording each one that wasn't met.
+    ///
+    /// # Safety
+    ///
+    /// `physical_device` must have been enumerated from `instance`, which must have been created
+    /// with `instance_api_version`.
+    pub(crate) unsafe fn check(
+        &self,
+        instance: &ash::Instance,
+        instance_api_version: u32,
+        physical_device: vk::PhysicalDevice,
+        properties: &vk::PhysicalDeviceProperties,
+        rejections: &mut Vec<RejectionReason>,
+    ) {
+        if self.api_version > 0 {
+            let meets_api_version = properties.api_version >= self.api_version;
+
+            log::info!(
+                "  Checking for api version >= {}: {} (device has {})",
+                format_api_version(self.api_version),
+                tick(meets_api_version),
+                format_api_version(properties.api_version)
+            );
+
+            if !meets_api_version {
+                rejections.push(RejectionReason::ApiVersionTooLow {
+                    required: format_api_version(self.api_version),
+                    supported: format_api_version(properties.api_version),
+                });
+            }
+        }
+
+        let mut supported_vulkan_1_1_features = vk::PhysicalDeviceVulkan11Features::default();
+        let mut supported_vulkan_1_2_features = vk::PhysicalDeviceVulkan12Features::default();
+
+        // Querying the 1.1 and 1.2 feature structs is only valid on 1.2 instances and devices.
+        // Otherwise, everything in them is treated as unsupported.
+        let supported_features = if self
+            .check_instance_api_version(instance_api_version, rejections)
+            && properties.api_version >= vk::API_VERSION_1_2
+        {
+            let mut features = vk::PhysicalDeviceFeatures2::builder()
+                .push_next(&mut supported_vulkan_1_1_features)
+                .push_next(&mut supported_vulkan_1_2_features);
+
+            instance.get_physical_device_features2(physical_device, &mut features);
+
+            features.features
+        } else {
+            instance.get_physical_device_features(physical_device)
+        };
+
+        for (name, features) in [
+            (
+                "core",
+                check_core_features(&self.features, &supported_features),
+            ),
+            (
+                "vulkan 1.1",
+                check_vulkan_1_1_features(
+                    &self.vulkan_1_1_features,
+                    &supported_vulkan_1_1_features,
+                ),
+            ),
+            (
+                "vulkan 1.2",
+                check_vulkan_1_2_features(
+                    &self.vulkan_1_2_features,
+                    &supported_vulkan_1_2_features,
+                ),
+            ),
+        ] {
+            if features.is_empty() {
+                continue;
+            }
+
+            log::info!("  Checking for required {} features:", name);
+
+            for (feature, supported) in features {
+                log::info!("    * {}: {}", feature, tick(supported));
+
+                if !supported {
+                    rejections.push(RejectionReason::MissingFeature(feature.to_string()));
+                }
+            }
+        }
+
+        self.limits.check(&properties.limits, rejections);
+    }
+}
+
+pub(crate) fn format_api_version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        vk::api_version_major(version),
+        vk::api_version_minor(version),
+        vk::api_version_patch(version)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_only_requested_features() {
+        let required = vk::PhysicalDeviceFeatures {
+            sampler_anisotropy: vk::TRUE,
+            shader_int64: vk::TRUE,
+            ..Default::default()
+        };
+        let supported = vk::PhysicalDeviceFeatures {
+            sampler_anisotropy: vk::TRUE,
+            geometry_shader: vk::TRUE,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            check_core_features(&required, &supported),
+            [("sampler_anisotropy", true), ("shader_int64", false)]
+        );
+        assert!(check_core_features(&Default::default(), &supported).is_empty());
+
+        let required = vk::PhysicalDeviceVulkan12Features {
+            buffer_device_address: vk::TRUE,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_vulkan_1_2_features(&required, &required),
+            [("buffer_device_address", true)]
+        );
+
+        let required = vk::PhysicalDeviceVulkan11Features {
+            multiview: vk::TRUE,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_vulkan_1_1_features(&required, &Default::default()),
+            [("multiview", false)]
+        );
+    }
+
+    #[test]
+    fn rejects_limits_that_are_too_low() {
+        let requirements = LimitRequirements {
+            max_push_constants_size: 256,
+            max_bound_descriptor_sets: 4,
+            max_sampler_anisotropy: 16.0,
+            ..Default::default()
+        };
+        let limits = vk::PhysicalDeviceLimits {
+            max_push_constants_size: 128,
+            max_bound_descriptor_sets: 8,
+            max_sampler_anisotropy: 16.0,
+            ..Default::default()
+        };
+
+        let mut rejections = Vec::new();
+        requirements.check(&limits, &mut rejections);
+
+        assert_eq!(
+            rejections,
+            [RejectionReason::LimitTooLow {
+                limit: "max_push_constants_size".to_string(),
+                required: 256.0,
+                supported: 128.0,
+            }]
+        );
+
+        // Limits that aren't set aren't checked.
+        let mut rejections = Vec::new();
+        LimitRequirements::default().check(&Default::default(), &mut rejections);
+        assert!(rejections.is_empty());
+    }
+
+    #[test]
+    fn vulkan_1_2_features_need_a_vulkan_1_2_instance() {
+        let mut requirements = DeviceRequirements::default();
+        let mut rejections = Vec::new();
+
+        assert!(!requirements.requests_vulkan_1_2_structs());
+        assert!(!requirements.check_instance_api_version(vk::API_VERSION_1_0, &mut rejections));
+        assert!(rejections.is_empty());
+
+        requirements.vulkan_1_2_features.buffer_device_address = vk::TRUE;
+
+        assert!(requirements.requests_vulkan_1_2_structs());
+        assert!(requirements.check_instance_api_version(vk::API_VERSION_1_2, &mut rejections));
+        assert!(rejections.is_empty());
+
+        assert!(!requirements.check_instance_api_version(vk::API_VERSION_1_1, &mut rejections));
+        assert_eq!(
+            rejections,
+            [RejectionReason::InstanceApiVersionTooLow {
+                required: "1.2.0".to_string(),
+                supported: "1.1.0".to_string(),
+            }]
+        );
+    }
+}
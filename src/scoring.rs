@@ -0,0 +1,192 @@
+use crate::{OwnedCStrList, QueueFamilies};
+use ash::vk;
+
+/// The environment variable that can be used to force the selection of a specific device.
+///
+/// The value is either a device index (as enumerated by the instance), a device UUID or a
+/// case-insensitive substring of the device name. See [`DeviceOverride::parse`].
+pub const DEVICE_OVERRIDE_ENV_VAR: &str = "AOA_DEVICE";
+
+/// Information about an eligible device that's given to a device scorer.
+pub struct DeviceScoringInfo<'a> {
+    pub physical_device: vk::PhysicalDevice,
+    pub properties: &'a vk::PhysicalDeviceProperties,
+    pub memory_properties: &'a vk::PhysicalDeviceMemoryProperties,
+    pub queue_families: &'a QueueFamilies,
+    /// The optional extensions that the device supports.
+    pub enabled_optional_extensions: &'a OwnedCStrList,
+}
+
+impl<'a> DeviceScoringInfo<'a> {
+    /// The size of the largest device-local memory heap, in bytes.
+    pub fn device_local_memory(&self) -> vk::DeviceSize {
+        self.memory_properties.memory_heaps[..self.memory_properties.memory_heap_count as usize]
+            .iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// The built-in device scorer.
+///
+/// Devices are ranked by type (discrete, then integrated, then everything else), then by the
+/// number of optional extensions they support, then by the size of their largest device-local
+/// heap, then by their api version.
+pub fn default_device_score(info: &DeviceScoringInfo) -> u64 {
+    let device_type: u64 = match info.properties.device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => 2,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 1,
+        _ => 0,
+    };
+
+    let optional_extensions = (info.enabled_optional_extensions.len() as u64).min(0xff);
+
+    let memory_in_mebibytes = (info.device_local_memory() / (1024 * 1024)).min(0xffff_ffff);
+
+    let api_version = (vk::api_version_major(info.properties.api_version) << 8
+        | vk::api_version_minor(info.properties.api_version))
+    .min(0xffff) as u64;
+
+    device_type << 56 | optional_extensions << 48 | memory_in_mebibytes << 16 | api_version
+}
+
+/// A way of forcing the selection of a specific device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceOverride {
+    /// The index of the device in `vkEnumeratePhysicalDevices`.
+    Index(usize),
+    /// The `deviceUUID` from `vk::PhysicalDeviceIDProperties`. Only matches if both the
+    /// instance and the device are at least Vulkan 1.1. The instance version is taken from
+    /// [`DeviceSelectionDescriptor::instance_api_version`](crate::DeviceSelectionDescriptor).
+    Uuid([u8; vk::UUID_SIZE]),
+    /// A case-insensitive substring of the device name.
+    Name(String),
+}
+
+impl DeviceOverride {
+    /// Read the override from the [`DEVICE_OVERRIDE_ENV_VAR`] environment variable.
+    pub fn from_env() -> Option<Self> {
+        std::env::var(DEVICE_OVERRIDE_ENV_VAR)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| Self::parse(&value))
+    }
+
+    /// Parse an override. Integers are treated as indices, 32 hex digits (optionally
+    /// hyphenated) are treated as UUIDs and everything else is treated as a name.
+    pub fn parse(value: &str) -> Self {
+        let value = value.trim();
+
+        if let Ok(index) = value.parse() {
+            return Self::Index(index);
+        }
+
+        if let Some(uuid) = parse_uuid(value) {
+            return Self::Uuid(uuid);
+        }
+
+        Self::Name(value.to_lowercase())
+    }
+
+    /// Check if a device matches the override. `device_uuid` is only needed for
+    /// [`DeviceOverride::Uuid`].
+    pub(crate) fn matches(
+        &self,
+        index: usize,
+        name: &str,
+        device_uuid: impl FnOnce() -> Option<[u8; vk::UUID_SIZE]>,
+    ) -> bool {
+        match self {
+            Self::Index(override_index) => *override_index == index,
+            Self::Uuid(uuid) => device_uuid().as_ref() == Some(uuid),
+            Self::Name(substring) => name.to_lowercase().contains(substring.as_str()),
+        }
+    }
+}
+
+fn parse_uuid(value: &str) -> Option<[u8; vk::UUID_SIZE]> {
+    let digits: Vec<u8> = value
+        .chars()
+        .filter(|&c| c != '-')
+        .map(|c| c.to_digit(16).map(|digit| digit as u8))
+        .collect::<Option<_>>()?;
+
+    if digits.len() != vk::UUID_SIZE * 2 {
+        return None;
+    }
+
+    let mut uuid = [0; vk::UUID_SIZE];
+
+    for (byte, pair) in uuid.iter_mut().zip(digits.chunks(2)) {
+        *byte = pair[0] << 4 | pair[1];
+    }
+
+    Some(uuid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: [u8; vk::UUID_SIZE] = [
+        0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0, 0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd,
+        0xef,
+    ];
+
+    #[test]
+    fn parses_indices() {
+        assert_eq!(DeviceOverride::parse("1"), DeviceOverride::Index(1));
+        assert_eq!(DeviceOverride::parse(" 0 \n"), DeviceOverride::Index(0));
+    }
+
+    #[test]
+    fn parses_uuids() {
+        assert_eq!(
+            DeviceOverride::parse("123456789abcdef00123456789abcdef"),
+            DeviceOverride::Uuid(UUID)
+        );
+        assert_eq!(
+            DeviceOverride::parse("12345678-9ABC-DEF0-0123-456789ABCDEF"),
+            DeviceOverride::Uuid(UUID)
+        );
+        // Too many digits for an index.
+        assert_eq!(
+            DeviceOverride::parse("12345678901234567890123456789012"),
+            DeviceOverride::Uuid([
+                0x12, 0x34, 0x56, 0x78, 0x90, 0x12, 0x34, 0x56, 0x78, 0x90, 0x12, 0x34, 0x56, 0x78,
+                0x90, 0x12
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_everything_else_as_names() {
+        assert_eq!(
+            DeviceOverride::parse("NVIDIA GeForce"),
+            DeviceOverride::Name("nvidia geforce".to_string())
+        );
+        // One digit short of a UUID.
+        assert_eq!(
+            DeviceOverride::parse("123456789abcdef00123456789abcde"),
+            DeviceOverride::Name("123456789abcdef00123456789abcde".to_string())
+        );
+        assert_eq!(
+            DeviceOverride::parse("-1"),
+            DeviceOverride::Name("-1".to_string())
+        );
+    }
+
+    #[test]
+    fn matches_devices() {
+        let no_uuid = || panic!("The UUID is only needed for UUID overrides");
+
+        assert!(DeviceOverride::Index(2).matches(2, "llvmpipe", no_uuid));
+        assert!(!DeviceOverride::Index(2).matches(1, "llvmpipe", no_uuid));
+        assert!(DeviceOverride::parse("LLVMpipe").matches(0, "llvmpipe (LLVM 15)", no_uuid));
+
+        assert!(DeviceOverride::Uuid(UUID).matches(0, "llvmpipe", || Some(UUID)));
+        assert!(!DeviceOverride::Uuid(UUID).matches(0, "llvmpipe", || None));
+    }
+}
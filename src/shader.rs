@@ -0,0 +1,181 @@
+use crate::{
+    load_shader_module, load_shader_module_checked, set_object_name, SpecializationConstants,
+};
+use ash::extensions::ext::DebugUtils as DebugUtilsLoader;
+use ash::vk;
+use std::ffi::CString;
+
+/// A shader module along with the entry points that are used from it. Unlike
+/// [`load_shader_module_as_stage`](crate::load_shader_module_as_stage), the module can be
+/// destroyed with [`Self::cleanup`] once every pipeline using it has been created.
+pub struct ShaderStage {
+    pub module: vk::ShaderModule,
+    entry_points: Vec<(vk::ShaderStageFlags, CString)>,
+    specialization: SpecializationConstants,
+}
+
+impl ShaderStage {
+    /// Load a module with a single entry point.
+    ///
+    /// Like [`load_shader_module`], this doesn't check the module's SPIR-V version, so a module
+    /// that's too new for the device fails with whatever error the driver gives. Use
+    /// [`Self::with_entry_points_checked`] for a descriptive error instead.
+    pub fn new(
+        bytes: &[u8],
+        name: &str,
+        stage: vk::ShaderStageFlags,
+        entry_point: &str,
+        device: &ash::Device,
+        debug_utils_loader: Option<&DebugUtilsLoader>,
+    ) -> anyhow::Result<Self> {
+        Self::with_entry_points(
+            bytes,
+            name,
+            &[(stage, entry_point)],
+            device,
+            debug_utils_loader,
+        )
+    }
+
+    /// Load a module that has several entry points, such as a WGSL file containing both a
+    /// vertex and a fragment shader. The SPIR-V version isn't checked, as in [`Self::new`].
+    pub fn with_entry_points(
+        bytes: &[u8],
+        name: &str,
+        entry_points: &[(vk::ShaderStageFlags, &str)],
+        device: &ash::Device,
+        debug_utils_loader: Option<&DebugUtilsLoader>,
+    ) -> anyhow::Result<Self> {
+        Self::load(bytes, name, entry_points, device, debug_utils_loader, None)
+    }
+
+    /// Like [`Self::with_entry_points`], but first checks that the module's SPIR-V version is
+    /// supported by `api_version`, as in [`load_shader_module_checked`].
+    pub fn with_entry_points_checked(
+        bytes: &[u8],
+        name: &str,
+        entry_points: &[(vk::ShaderStageFlags, &str)],
+        device: &ash::Device,
+        debug_utils_loader: Option<&DebugUtilsLoader>,
+        api_version: u32,
+    ) -> anyhow::Result<Self> {
+        Self::load(
+            bytes,
+            name,
+            entry_points,
+            device,
+            debug_utils_loader,
+            Some(api_version),
+        )
+    }
+
+    pub(crate) fn load(
+        bytes: &[u8],
+        name: &str,
+        entry_points: &[(vk::ShaderStageFlags, &str)],
+        device: &ash::Device,
+        debug_utils_loader: Option<&DebugUtilsLoader>,
+        api_version: Option<u32>,
+    ) -> anyhow::Result<Self> {
+        if entry_points.is_empty() {
+            return Err(anyhow::anyhow!("Shader {:?} has no entry points", name));
+        }
+
+        let entry_points = entry_points
+            .iter()
+            .map(|&(stage, entry_point)| Ok((stage, CString::new(entry_point)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let module = match api_version {
+            Some(api_version) => load_shader_module_checked(bytes, device, api_version)?,
+            None => load_shader_module(bytes, device)?,
+        };
+
+        if let Some(debug_utils_loader) = debug_utils_loader {
+            if let Err(error) = set_object_name(device, debug_utils_loader, module, name) {
+                unsafe { device.destroy_shader_module(module, None) };
+                return Err(error);
+            }
+        }
+
+        Ok(Self {
+            module,
+            entry_points,
+            specialization: SpecializationConstants::new(),
+        })
+    }
+
+    /// Use a set of specialization constants for every entry point.
+    pub fn specialize(mut self, constants: SpecializationConstants) -> Self {
+        self.specialization = constants;
+        self
+    }
+
+    pub fn specialization(&self) -> &SpecializationConstants {
+        &self.specialization
+    }
+
+    pub fn entry_points(&self) -> impl Iterator<Item = (vk::ShaderStageFlags, &str)> + '_ {
+        self.entry_points
+            .iter()
+            .map(|(stage, entry_point)| (*stage, entry_point.to_str().unwrap()))
+    }
+
+    /// The create info for the first entry point.
+    pub fn create_info(&self) -> vk::PipelineShaderStageCreateInfoBuilder<'_> {
+        let (stage, entry_point) = &self.entry_points[0];
+        self.create_info_inner(*stage, entry_point)
+    }
+
+    /// The create info for a named entry point.
+    pub fn entry_point_create_info(
+        &self,
+        entry_point: &str,
+    ) -> Option<vk::PipelineShaderStageCreateInfoBuilder<'_>> {
+        self.entry_points
+            .iter()
+            .find(|(_, name)| name.as_bytes() == entry_point.as_bytes())
+            .map(|(stage, name)| self.create_info_inner(*stage, name))
+    }
+
+    /// The create infos for every entry point, in the order that they were given.
+    pub fn create_infos(&self) -> Vec<vk::PipelineShaderStageCreateInfoBuilder<'_>> {
+        self.entry_points
+            .iter()
+            .map(|(stage, name)| self.create_info_inner(*stage, name))
+            .collect()
+    }
+
+    fn create_info_inner<'a>(
+        &'a self,
+        stage: vk::ShaderStageFlags,
+        entry_point: &'a CString,
+    ) -> vk::PipelineShaderStageCreateInfoBuilder<'a> {
+        let builder = vk::PipelineShaderStageCreateInfo::builder()
+            .module(self.module)
+            .stage(stage)
+            .name(entry_point);
+
+        if self.specialization.is_empty() {
+            builder
+        } else {
+            builder.specialization_info(self.specialization.info())
+        }
+    }
+
+    pub fn cleanup(&self, device: &ash::Device) {
+        unsafe { device.destroy_shader_module(self.module, None) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn stages_are_send_and_sync() {
+        assert_send_sync::<ShaderStage>();
+    }
+}
@@ -0,0 +1,218 @@
+use crate::load_shader_module_as_stage;
+use ash::vk;
+use std::ffi::CStr;
+
+/// A typed specialization constant value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpecializationConstant {
+    Bool(bool),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+}
+
+impl SpecializationConstant {
+    fn to_bytes(self) -> [u8; 4] {
+        match self {
+            // Boolean constants are read as a `VkBool32`.
+            Self::Bool(value) => (value as vk::Bool32).to_ne_bytes(),
+            Self::U32(value) => value.to_ne_bytes(),
+            Self::I32(value) => value.to_ne_bytes(),
+            Self::F32(value) => value.to_ne_bytes(),
+        }
+    }
+}
+
+impl From<bool> for SpecializationConstant {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<u32> for SpecializationConstant {
+    fn from(value: u32) -> Self {
+        Self::U32(value)
+    }
+}
+
+impl From<i32> for SpecializationConstant {
+    fn from(value: i32) -> Self {
+        Self::I32(value)
+    }
+}
+
+impl From<f32> for SpecializationConstant {
+    fn from(value: f32) -> Self {
+        Self::F32(value)
+    }
+}
+
+/// A set of specialization constants packed into a `vk::SpecializationInfo` that points into
+/// the set itself.
+#[derive(Default, Debug)]
+pub struct SpecializationConstants {
+    constants: Vec<(u32, SpecializationConstant)>,
+    map_entries: Vec<vk::SpecializationMapEntry>,
+    data: Vec<u8>,
+    info: vk::SpecializationInfo,
+}
+
+impl SpecializationConstants {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the value of the constant with a `constant_id`, replacing any previous value.
+    pub fn set(&mut self, id: u32, value: impl Into<SpecializationConstant>) -> &mut Self {
+        let value = value.into();
+
+        match self
+            .constants
+            .iter()
+            .position(|&(constant_id, _)| constant_id == id)
+        {
+            Some(index) => {
+                self.constants[index].1 = value;
+
+                let offset = self.map_entries[index].offset as usize;
+                self.data[offset..offset + 4].copy_from_slice(&value.to_bytes());
+            }
+            None => {
+                self.constants.push((id, value));
+
+                self.map_entries.push(vk::SpecializationMapEntry {
+                    constant_id: id,
+                    offset: self.data.len() as u32,
+                    size: 4,
+                });
+
+                self.data.extend_from_slice(&value.to_bytes());
+            }
+        }
+
+        // The vectors may have been reallocated.
+        self.info = *vk::SpecializationInfo::builder()
+            .map_entries(&self.map_entries)
+            .data(&self.data);
+
+        self
+    }
+
+    /// Builder-style version of [`Self::set`].
+    pub fn with(mut self, id: u32, value: impl Into<SpecializationConstant>) -> Self {
+        self.set(id, value);
+        self
+    }
+
+    pub fn get(&self, id: u32) -> Option<SpecializationConstant> {
+        self.constants
+            .iter()
+            .find(|&&(constant_id, _)| constant_id == id)
+            .map(|&(_, value)| value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, SpecializationConstant)> + '_ {
+        self.constants.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.constants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.constants.is_empty()
+    }
+
+    /// The `vk::SpecializationInfo` for the constants, valid for as long as `self` is borrowed.
+    pub fn info(&self) -> &vk::SpecializationInfo {
+        &self.info
+    }
+}
+
+// SAFETY: The only non-`Send`/`Sync` field is `info`, whose pointers point into the heap buffers
+// of `map_entries` and `data`. Those move with the struct, are only mutated through `&mut self`
+// (which rebuilds `info`), and are never freed while `info` can be read.
+unsafe impl Send for SpecializationConstants {}
+unsafe impl Sync for SpecializationConstants {}
+
+impl Clone for SpecializationConstants {
+    fn clone(&self) -> Self {
+        let mut constants = Self::new();
+
+        for (id, value) in self.iter() {
+            constants.set(id, value);
+        }
+
+        constants
+    }
+}
+
+/// Like [`load_shader_module_as_stage`], but with specialization constants. The returned builder
+/// borrows `constants`.
+pub fn load_shader_module_as_specialized_stage<'a>(
+    bytes: &[u8],
+    stage: vk::ShaderStageFlags,
+    device: &ash::Device,
+    entry_point: &'a CStr,
+    constants: &'a SpecializationConstants,
+) -> anyhow::Result<vk::PipelineShaderStageCreateInfoBuilder<'a>> {
+    let stage = load_shader_module_as_stage(bytes, stage, device, entry_point)?;
+
+    Ok(if constants.is_empty() {
+        stage
+    } else {
+        stage.specialization_info(constants.info())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn constants_are_send_and_sync() {
+        assert_send_sync::<SpecializationConstants>();
+    }
+
+    fn packed(constants: &SpecializationConstants) -> (Vec<(u32, u32)>, Vec<u8>) {
+        let info = constants.info();
+
+        let map_entries = unsafe {
+            std::slice::from_raw_parts(info.p_map_entries, info.map_entry_count as usize)
+        };
+        let data = unsafe { std::slice::from_raw_parts(info.p_data as *const u8, info.data_size) };
+
+        (
+            map_entries
+                .iter()
+                .map(|entry| (entry.constant_id, entry.offset))
+                .collect(),
+            data.to_vec(),
+        )
+    }
+
+    #[test]
+    fn packs_and_replaces_constants() {
+        let mut constants = SpecializationConstants::new()
+            .with(3, true)
+            .with(7, 2.0f32)
+            .with(1, -1i32);
+
+        constants.set(7, 0.5f32);
+
+        let (map_entries, data) = packed(&constants);
+
+        assert_eq!(map_entries, [(3, 0), (7, 4), (1, 8)]);
+        assert_eq!(data[0..4], 1u32.to_ne_bytes());
+        assert_eq!(data[4..8], 0.5f32.to_ne_bytes());
+        assert_eq!(data[8..12], (-1i32).to_ne_bytes());
+        assert_eq!(constants.get(7), Some(SpecializationConstant::F32(0.5)));
+
+        let clone = constants.clone();
+        drop(constants);
+
+        assert_eq!(packed(&clone), (map_entries, data));
+    }
+}
@@ -0,0 +1,405 @@
+use crate::requirements::format_api_version;
+use ash::vk;
+
+const MAGIC_NUMBER: u32 = 0x0723_0203;
+pub(crate) const HEADER_WORDS: usize = 5;
+const OP_CAPABILITY: u32 = 17;
+
+/// Read SPIR-V bytes into words, checking the length and magic number first.
+pub fn read_spirv(bytes: &[u8]) -> anyhow::Result<Vec<u32>> {
+    if bytes.len() % 4 != 0 {
+        return Err(anyhow::anyhow!(
+            "SPIR-V is {} bytes long, which is not a whole number of words. The file may be \
+             truncated",
+            bytes.len()
+        ));
+    }
+
+    if bytes.len() < HEADER_WORDS * 4 {
+        return Err(anyhow::anyhow!(
+            "SPIR-V is {} bytes long, which is too short for a header",
+            bytes.len()
+        ));
+    }
+
+    let first_word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+    if first_word != MAGIC_NUMBER && first_word != MAGIC_NUMBER.swap_bytes() {
+        return Err(anyhow::anyhow!(
+            "Expected the SPIR-V magic number {:#010x} but found {:#010x}. This is not a SPIR-V \
+             file",
+            MAGIC_NUMBER,
+            first_word
+        ));
+    }
+
+    Ok(ash::util::read_spv(&mut std::io::Cursor::new(bytes))?)
+}
+
+/// Check the length and magic number of SPIR-V words, as returned by [`read_spirv`].
+pub(crate) fn check_header(spv: &[u32]) -> anyhow::Result<()> {
+    if spv.len() < HEADER_WORDS {
+        return Err(anyhow::anyhow!(
+            "SPIR-V is {} words long, which is too short for a header",
+            spv.len()
+        ));
+    }
+
+    if spv[0] != MAGIC_NUMBER {
+        return Err(anyhow::anyhow!(
+            "Expected the SPIR-V magic number {:#010x} but found {:#010x}. This is not a SPIR-V \
+             module",
+            MAGIC_NUMBER,
+            spv[0]
+        ));
+    }
+
+    Ok(())
+}
+
+/// The highest SPIR-V version that a Vulkan version is guaranteed to accept. Devices may
+/// accept more through `VK_KHR_spirv_1_4`.
+pub fn max_spirv_version(api_version: u32) -> (u32, u32) {
+    match (
+        vk::api_version_major(api_version),
+        vk::api_version_minor(api_version),
+    ) {
+        (1, 0) => (1, 0),
+        (1, 1) => (1, 3),
+        (1, 2) => (1, 5),
+        _ => (1, 6),
+    }
+}
+
+/// The header and declared capabilities of a SPIR-V module.
+#[derive(Clone, Debug)]
+pub struct SpirvModuleInfo {
+    /// The (major, minor) version.
+    pub version: (u32, u32),
+    pub generator: u32,
+    pub capabilities: Vec<u32>,
+}
+
+impl SpirvModuleInfo {
+    pub fn parse(spv: &[u32]) -> anyhow::Result<Self> {
+        check_header(spv)?;
+
+        let version = ((spv[1] >> 16) & 0xff, (spv[1] >> 8) & 0xff);
+
+        let mut capabilities = Vec::new();
+        let mut offset = HEADER_WORDS;
+
+        // Capabilities are declared before anything else, so stop at the first other
+        // instruction.
+        while let Some(&word) = spv.get(offset) {
+            let word_count = (word >> 16) as usize;
+
+            if word & 0xffff != OP_CAPABILITY {
+                break;
+            }
+
+            match spv.get(offset + 1) {
+                Some(&capability) if word_count == 2 => capabilities.push(capability),
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Malformed OpCapability instruction at word {}",
+                        offset
+                    ))
+                }
+            }
+
+            offset += word_count;
+        }
+
+        Ok(Self {
+            version,
+            generator: spv[2],
+            capabilities,
+        })
+    }
+
+    pub fn capability_names(&self) -> Vec<String> {
+        self.capabilities
+            .iter()
+            .map(|&capability| match capability_name(capability) {
+                Some(name) => name.to_string(),
+                None => format!("Capability({})", capability),
+            })
+            .collect()
+    }
+
+    /// Fail if the module's SPIR-V version is newer than `api_version` accepts.
+    pub fn check_api_version(&self, api_version: u32) -> anyhow::Result<()> {
+        let max_version = max_spirv_version(api_version);
+
+        if self.version > max_version {
+            return Err(anyhow::anyhow!(
+                "The module is SPIR-V {}.{} but Vulkan {} only accepts up to SPIR-V {}.{} \
+                 (capabilities: {})",
+                self.version.0,
+                self.version.1,
+                format_api_version(api_version),
+                max_version.0,
+                max_version.1,
+                self.capability_names().join(", ")
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Like [`load_shader_module`](crate::load_shader_module), but also checks that the module's
+/// SPIR-V version is supported by `api_version`, which should be the lower of the instance and
+/// device API versions. Errors name the module version and capabilities.
+pub fn load_shader_module_checked(
+    bytes: &[u8],
+    device: &ash::Device,
+    api_version: u32,
+) -> anyhow::Result<vk::ShaderModule> {
+    let spv = read_spirv(bytes)?;
+    let info = SpirvModuleInfo::parse(&spv)?;
+
+    info.check_api_version(api_version)?;
+
+    unsafe { device.create_shader_module(&vk::ShaderModuleCreateInfo::builder().code(&spv), None) }
+        .map_err(|error| {
+            anyhow::anyhow!(
+                "Creating a shader module from SPIR-V {}.{} with capabilities [{}] failed: {}",
+                info.version.0,
+                info.version.1,
+                info.capability_names().join(", "),
+                error
+            )
+        })
+}
+
+fn capability_name(capability: u32) -> Option<&'static str> {
+    Some(match capability {
+        0 => "Matrix",
+        1 => "Shader",
+        2 => "Geometry",
+        3 => "Tessellation",
+        4 => "Addresses",
+        5 => "Linkage",
+        6 => "Kernel",
+        9 => "Float16",
+        10 => "Float64",
+        11 => "Int64",
+        12 => "Int64Atomics",
+        21 => "AtomicStorage",
+        22 => "Int16",
+        23 => "TessellationPointSize",
+        24 => "GeometryPointSize",
+        25 => "ImageGatherExtended",
+        27 => "StorageImageMultisample",
+        28 => "UniformBufferArrayDynamicIndexing",
+        29 => "SampledImageArrayDynamicIndexing",
+        30 => "StorageBufferArrayDynamicIndexing",
+        31 => "StorageImageArrayDynamicIndexing",
+        32 => "ClipDistance",
+        33 => "CullDistance",
+        34 => "ImageCubeArray",
+        35 => "SampleRateShading",
+        36 => "ImageRect",
+        37 => "SampledRect",
+        39 => "Int8",
+        40 => "InputAttachment",
+        41 => "SparseResidency",
+        42 => "MinLod",
+        43 => "Sampled1D",
+        44 => "Image1D",
+        45 => "SampledCubeArray",
+        46 => "SampledBuffer",
+        47 => "ImageBuffer",
+        48 => "ImageMSArray",
+        49 => "StorageImageExtendedFormats",
+        50 => "ImageQuery",
+        51 => "DerivativeControl",
+        52 => "InterpolationFunction",
+        53 => "TransformFeedback",
+        54 => "GeometryStreams",
+        55 => "StorageImageReadWithoutFormat",
+        56 => "StorageImageWriteWithoutFormat",
+        57 => "MultiViewport",
+        61 => "GroupNonUniform",
+        62 => "GroupNonUniformVote",
+        63 => "GroupNonUniformArithmetic",
+        64 => "GroupNonUniformBallot",
+        65 => "GroupNonUniformShuffle",
+        66 => "GroupNonUniformShuffleRelative",
+        67 => "GroupNonUniformClustered",
+        68 => "GroupNonUniformQuad",
+        69 => "ShaderLayer",
+        70 => "ShaderViewportIndex",
+        4422 => "FragmentShadingRateKHR",
+        4423 => "SubgroupBallotKHR",
+        4427 => "DrawParameters",
+        4431 => "SubgroupVoteKHR",
+        4433 => "StorageBuffer16BitAccess",
+        4434 => "UniformAndStorageBuffer16BitAccess",
+        4435 => "StoragePushConstant16",
+        4436 => "StorageInputOutput16",
+        4437 => "DeviceGroup",
+        4439 => "MultiView",
+        4441 => "VariablePointersStorageBuffer",
+        4442 => "VariablePointers",
+        4448 => "StorageBuffer8BitAccess",
+        4449 => "UniformAndStorageBuffer8BitAccess",
+        4450 => "StoragePushConstant8",
+        4464 => "DenormPreserve",
+        4465 => "DenormFlushToZero",
+        4466 => "SignedZeroInfNanPreserve",
+        4467 => "RoundingModeRTE",
+        4468 => "RoundingModeRTZ",
+        4472 => "RayQueryKHR",
+        4478 => "RayTraversalPrimitiveCullingKHR",
+        4479 => "RayTracingKHR",
+        5013 => "StencilExportEXT",
+        5016 => "Int64ImageEXT",
+        5055 => "ShaderClockKHR",
+        5254 => "ShaderViewportIndexLayerEXT",
+        5266 => "MeshShadingNV",
+        5283 => "MeshShadingEXT",
+        5284 => "FragmentBarycentricKHR",
+        5291 => "FragmentDensityEXT",
+        5301 => "ShaderNonUniform",
+        5302 => "RuntimeDescriptorArray",
+        5303 => "InputAttachmentArrayDynamicIndexing",
+        5304 => "UniformTexelBufferArrayDynamicIndexing",
+        5305 => "StorageTexelBufferArrayDynamicIndexing",
+        5306 => "UniformBufferArrayNonUniformIndexing",
+        5307 => "SampledImageArrayNonUniformIndexing",
+        5308 => "StorageBufferArrayNonUniformIndexing",
+        5309 => "StorageImageArrayNonUniformIndexing",
+        5310 => "InputAttachmentArrayNonUniformIndexing",
+        5311 => "UniformTexelBufferArrayNonUniformIndexing",
+        5312 => "StorageTexelBufferArrayNonUniformIndexing",
+        5340 => "RayTracingNV",
+        5345 => "VulkanMemoryModel",
+        5346 => "VulkanMemoryModelDeviceScope",
+        5347 => "PhysicalStorageBufferAddresses",
+        5363 => "FragmentShaderSampleInterlockEXT",
+        5372 => "FragmentShaderShadingRateInterlockEXT",
+        5378 => "FragmentShaderPixelInterlockEXT",
+        5379 => "DemoteToHelperInvocation",
+        6016 => "DotProductInputAll",
+        6017 => "DotProductInput4x8Bit",
+        6018 => "DotProductInput4x8BitPacked",
+        6019 => "DotProduct",
+        6033 => "AtomicFloat32AddEXT",
+        6034 => "AtomicFloat64AddEXT",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(version: (u32, u32), instructions: &[u32]) -> Vec<u32> {
+        let mut spv = vec![MAGIC_NUMBER, version.0 << 16 | version.1 << 8, 7, 1, 0];
+        spv.extend_from_slice(instructions);
+        spv
+    }
+
+    fn bytes(spv: &[u32]) -> Vec<u8> {
+        spv.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let valid = bytes(&module((1, 0), &[]));
+
+        let error = read_spirv(&valid[..valid.len() - 1]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "SPIR-V is 19 bytes long, which is not a whole number of words. The file may be \
+             truncated"
+        );
+
+        let error = read_spirv(&valid[..16]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "SPIR-V is 16 bytes long, which is too short for a header"
+        );
+
+        let mut wrong_magic = valid;
+        wrong_magic[..4].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+        let error = read_spirv(&wrong_magic).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Expected the SPIR-V magic number 0x07230203 but found 0x12345678. This is not a \
+             SPIR-V file"
+        );
+    }
+
+    #[test]
+    fn reads_byte_swapped_files() {
+        let spv = module((1, 3), &[2 << 16 | OP_CAPABILITY, 1]);
+        let swapped: Vec<u8> = spv.iter().flat_map(|word| word.to_be_bytes()).collect();
+
+        assert_eq!(read_spirv(&swapped).unwrap(), spv);
+        assert_eq!(read_spirv(&bytes(&spv)).unwrap(), spv);
+    }
+
+    #[test]
+    fn parses_capabilities_up_to_the_first_other_instruction() {
+        // OpCapability Shader, OpCapability 9999, then OpExtInstImport and a capability after it.
+        let spv = module(
+            (1, 5),
+            &[
+                2 << 16 | OP_CAPABILITY,
+                1,
+                2 << 16 | OP_CAPABILITY,
+                9999,
+                3 << 16 | 11,
+                0,
+                0,
+                2 << 16 | OP_CAPABILITY,
+                10,
+            ],
+        );
+
+        let info = SpirvModuleInfo::parse(&spv).unwrap();
+        assert_eq!(info.version, (1, 5));
+        assert_eq!(info.generator, 7);
+        assert_eq!(info.capabilities, [1, 9999]);
+        assert_eq!(info.capability_names(), ["Shader", "Capability(9999)"]);
+
+        let error =
+            SpirvModuleInfo::parse(&module((1, 0), &[3 << 16 | OP_CAPABILITY, 1, 0])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Malformed OpCapability instruction at word 5"
+        );
+
+        let error =
+            SpirvModuleInfo::parse(&module((1, 0), &[2 << 16 | OP_CAPABILITY])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Malformed OpCapability instruction at word 5"
+        );
+    }
+
+    #[test]
+    fn rejects_versions_that_are_too_new() {
+        let spv = module(
+            (1, 4),
+            &[2 << 16 | OP_CAPABILITY, 1, 2 << 16 | OP_CAPABILITY, 10],
+        );
+        let info = SpirvModuleInfo::parse(&spv).unwrap();
+
+        assert!(info.check_api_version(vk::API_VERSION_1_2).is_ok());
+        assert_eq!(
+            info.check_api_version(vk::API_VERSION_1_1)
+                .unwrap_err()
+                .to_string(),
+            "The module is SPIR-V 1.4 but Vulkan 1.1.0 only accepts up to SPIR-V 1.3 \
+             (capabilities: Shader, Float64)"
+        );
+
+        assert_eq!(max_spirv_version(vk::API_VERSION_1_0), (1, 0));
+        assert_eq!(max_spirv_version(vk::make_api_version(0, 1, 3, 0)), (1, 6));
+    }
+}
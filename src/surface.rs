@@ -0,0 +1,447 @@
+use ash::extensions::khr::Surface as SurfaceLoader;
+use ash::vk;
+
+/// HDR10 (ST2084 PQ) with a 10-bit format.
+///
+/// This and [`SCRGB_LINEAR`] require the `VK_EXT_swapchain_colorspace` instance extension.
+pub const HDR10_ST2084: vk::SurfaceFormatKHR = vk::SurfaceFormatKHR {
+    format: vk::Format::A2B10G10R10_UNORM_PACK32,
+    color_space: vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+};
+
+/// scRGB with a linear, extended range half-float format.
+pub const SCRGB_LINEAR: vk::SurfaceFormatKHR = vk::SurfaceFormatKHR {
+    format: vk::Format::R16G16B16A16_SFLOAT,
+    color_space: vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+};
+
+pub const BGRA8_SRGB: vk::SurfaceFormatKHR = vk::SurfaceFormatKHR {
+    format: vk::Format::B8G8R8A8_SRGB,
+    color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+};
+
+/// A surface format, along with which preference it matched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SurfaceFormatSelection {
+    pub surface_format: vk::SurfaceFormatKHR,
+    /// The index into the preference list of the matched format, or `None` if none of the
+    /// preferences were available and the first supported format was used instead.
+    pub preference_index: Option<usize>,
+}
+
+impl SurfaceFormatSelection {
+    pub fn is_fallback(&self) -> bool {
+        self.preference_index.is_none()
+    }
+
+    pub(crate) fn select(
+        supported: &[vk::SurfaceFormatKHR],
+        preferences: &[vk::SurfaceFormatKHR],
+    ) -> Option<Self> {
+        // A single undefined format means that any format can be used, but only in the color
+        // space that the surface reported.
+        if let [only] = supported {
+            if only.format == vk::Format::UNDEFINED {
+                if let Some((i, preference)) = preferences
+                    .iter()
+                    .enumerate()
+                    .find(|(_, preference)| preference.color_space == only.color_space)
+                {
+                    return Some(Self {
+                        surface_format: *preference,
+                        preference_index: Some(i),
+                    });
+                }
+            }
+        }
+
+        preferences
+            .iter()
+            .enumerate()
+            .find(|(_, preference)| supported.contains(preference))
+            .map(|(i, preference)| Self {
+                surface_format: *preference,
+                preference_index: Some(i),
+            })
+            .or_else(|| {
+                supported.first().map(|surface_format| Self {
+                    surface_format: *surface_format,
+                    preference_index: None,
+                })
+            })
+    }
+}
+
+/// How frames should be paced when presenting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresentModePreference {
+    /// `FIFO`. Always supported.
+    Vsync,
+    /// `MAILBOX` if supported, otherwise `FIFO`.
+    LowLatency,
+    /// `IMMEDIATE` if supported, then `MAILBOX`, then `FIFO`.
+    Uncapped,
+}
+
+impl PresentModePreference {
+    fn candidates(self) -> &'static [vk::PresentModeKHR] {
+        match self {
+            Self::Vsync => &[vk::PresentModeKHR::FIFO],
+            Self::LowLatency => &[vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::FIFO],
+            Self::Uncapped => &[
+                vk::PresentModeKHR::IMMEDIATE,
+                vk::PresentModeKHR::MAILBOX,
+                vk::PresentModeKHR::FIFO,
+            ],
+        }
+    }
+}
+
+pub struct SwapchainDescriptor {
+    pub surface_format: vk::SurfaceFormatKHR,
+    pub present_mode: PresentModePreference,
+    /// The size of the window. This is only used if the surface doesn't dictate the extent.
+    pub width: u32,
+    pub height: u32,
+    pub usage: vk::ImageUsageFlags,
+    /// The swapchain being replaced, if any.
+    pub old_swapchain: vk::SwapchainKHR,
+}
+
+/// Build a `vk::SwapchainCreateInfoKHR` for a surface, choosing the present mode, extent, image
+/// count, pre-transform and composite alpha from what the surface supports.
+///
+/// The result is suitable for passing straight to [`Swapchain::new`](crate::Swapchain::new).
+/// Fails if the extent is zero, which happens while the window is minimized.
+pub fn negotiate_swapchain_create_info(
+    physical_device: vk::PhysicalDevice,
+    surface_loader: &SurfaceLoader,
+    surface: vk::SurfaceKHR,
+    descriptor: &SwapchainDescriptor,
+) -> anyhow::Result<vk::SwapchainCreateInfoKHR> {
+    let (capabilities, present_modes) = unsafe {
+        (
+            surface_loader.get_physical_device_surface_capabilities(physical_device, surface)?,
+            surface_loader.get_physical_device_surface_present_modes(physical_device, surface)?,
+        )
+    };
+
+    swapchain_create_info(surface, &capabilities, &present_modes, descriptor)
+}
+
+/// The part of [`negotiate_swapchain_create_info`] that runs after the surface has been queried.
+pub(crate) fn swapchain_create_info(
+    surface: vk::SurfaceKHR,
+    capabilities: &vk::SurfaceCapabilitiesKHR,
+    present_modes: &[vk::PresentModeKHR],
+    descriptor: &SwapchainDescriptor,
+) -> anyhow::Result<vk::SwapchainCreateInfoKHR> {
+    if !capabilities
+        .supported_usage_flags
+        .contains(descriptor.usage)
+    {
+        return Err(anyhow::anyhow!(
+            "Swapchain usage {:?} is not supported by the surface (supported: {:?})",
+            descriptor.usage,
+            capabilities.supported_usage_flags
+        ));
+    }
+
+    let present_mode = descriptor
+        .present_mode
+        .candidates()
+        .iter()
+        .copied()
+        .find(|present_mode| present_modes.contains(present_mode))
+        .unwrap_or(vk::PresentModeKHR::FIFO);
+
+    // A current extent of u32::MAX means that the surface size is determined by the swapchain.
+    let image_extent = if capabilities.current_extent.width != u32::MAX {
+        capabilities.current_extent
+    } else {
+        vk::Extent2D {
+            width: descriptor.width.clamp(
+                capabilities.min_image_extent.width,
+                capabilities.max_image_extent.width,
+            ),
+            height: descriptor.height.clamp(
+                capabilities.min_image_extent.height,
+                capabilities.max_image_extent.height,
+            ),
+        }
+    };
+
+    if image_extent.width == 0 || image_extent.height == 0 {
+        return Err(anyhow::anyhow!(
+            "The swapchain extent is {}x{}, which usually means that the window is minimized. \
+             Wait until it has a non-zero size before creating the swapchain",
+            image_extent.width,
+            image_extent.height
+        ));
+    }
+
+    let mut min_image_count = capabilities.min_image_count + 1;
+
+    // A max image count of 0 means that there is no maximum.
+    if capabilities.max_image_count > 0 {
+        min_image_count = min_image_count.min(capabilities.max_image_count);
+    }
+
+    let pre_transform = if capabilities
+        .supported_transforms
+        .contains(vk::SurfaceTransformFlagsKHR::IDENTITY)
+    {
+        vk::SurfaceTransformFlagsKHR::IDENTITY
+    } else {
+        capabilities.current_transform
+    };
+
+    let composite_alpha = [
+        vk::CompositeAlphaFlagsKHR::OPAQUE,
+        vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+        vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED,
+        vk::CompositeAlphaFlagsKHR::INHERIT,
+    ]
+    .into_iter()
+    .find(|&composite_alpha| {
+        capabilities
+            .supported_composite_alpha
+            .contains(composite_alpha)
+    })
+    .unwrap_or(vk::CompositeAlphaFlagsKHR::OPAQUE);
+
+    log::debug!(
+        "Swapchain: {:?}, {}x{}, {} images, {:?}",
+        present_mode,
+        image_extent.width,
+        image_extent.height,
+        min_image_count,
+        descriptor.surface_format
+    );
+
+    Ok(*vk::SwapchainCreateInfoKHR::builder()
+        .surface(surface)
+        .min_image_count(min_image_count)
+        .image_format(descriptor.surface_format.format)
+        .image_color_space(descriptor.surface_format.color_space)
+        .image_extent(image_extent)
+        .image_array_layers(1)
+        .image_usage(descriptor.usage)
+        .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .pre_transform(pre_transform)
+        .composite_alpha(composite_alpha)
+        .present_mode(present_mode)
+        .clipped(true)
+        .old_swapchain(descriptor.old_swapchain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RGBA8_SRGB: vk::SurfaceFormatKHR = vk::SurfaceFormatKHR {
+        format: vk::Format::R8G8B8A8_SRGB,
+        color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+    };
+
+    #[test]
+    fn selects_the_first_available_preference() {
+        let selection =
+            SurfaceFormatSelection::select(&[RGBA8_SRGB, BGRA8_SRGB], &[HDR10_ST2084, BGRA8_SRGB])
+                .unwrap();
+
+        assert_eq!(selection.surface_format, BGRA8_SRGB);
+        assert_eq!(selection.preference_index, Some(1));
+        assert!(!selection.is_fallback());
+    }
+
+    #[test]
+    fn falls_back_to_the_first_supported_format() {
+        let selection =
+            SurfaceFormatSelection::select(&[RGBA8_SRGB, BGRA8_SRGB], &[HDR10_ST2084]).unwrap();
+
+        assert_eq!(selection.surface_format, RGBA8_SRGB);
+        assert!(selection.is_fallback());
+
+        assert_eq!(SurfaceFormatSelection::select(&[], &[BGRA8_SRGB]), None);
+    }
+
+    #[test]
+    fn a_single_undefined_format_allows_any_preference_in_its_color_space() {
+        let undefined = vk::SurfaceFormatKHR {
+            format: vk::Format::UNDEFINED,
+            color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        };
+
+        let selection =
+            SurfaceFormatSelection::select(&[undefined], &[RGBA8_SRGB, BGRA8_SRGB]).unwrap();
+
+        assert_eq!(selection.surface_format, RGBA8_SRGB);
+        assert_eq!(selection.preference_index, Some(0));
+
+        // HDR10 is skipped because the surface only reported sRGB.
+        let selection =
+            SurfaceFormatSelection::select(&[undefined], &[HDR10_ST2084, BGRA8_SRGB]).unwrap();
+
+        assert_eq!(selection.surface_format, BGRA8_SRGB);
+        assert_eq!(selection.preference_index, Some(1));
+
+        // Without a preference in the right color space, the undefined format is all there is
+        // to fall back to.
+        for preferences in [&[][..], &[HDR10_ST2084, SCRGB_LINEAR]] {
+            let selection = SurfaceFormatSelection::select(&[undefined], preferences).unwrap();
+            assert_eq!(selection.surface_format, undefined);
+            assert!(selection.is_fallback());
+        }
+    }
+
+    fn descriptor() -> SwapchainDescriptor {
+        SwapchainDescriptor {
+            surface_format: BGRA8_SRGB,
+            present_mode: PresentModePreference::LowLatency,
+            width: 1280,
+            height: 720,
+            usage: vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            old_swapchain: vk::SwapchainKHR::null(),
+        }
+    }
+
+    fn capabilities() -> vk::SurfaceCapabilitiesKHR {
+        vk::SurfaceCapabilitiesKHR {
+            min_image_count: 2,
+            max_image_count: 8,
+            current_extent: vk::Extent2D {
+                width: 800,
+                height: 600,
+            },
+            min_image_extent: vk::Extent2D {
+                width: 1,
+                height: 1,
+            },
+            max_image_extent: vk::Extent2D {
+                width: 4096,
+                height: 4096,
+            },
+            max_image_array_layers: 1,
+            supported_transforms: vk::SurfaceTransformFlagsKHR::IDENTITY
+                | vk::SurfaceTransformFlagsKHR::ROTATE_90,
+            current_transform: vk::SurfaceTransformFlagsKHR::ROTATE_90,
+            supported_composite_alpha: vk::CompositeAlphaFlagsKHR::INHERIT,
+            supported_usage_flags: vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | vk::ImageUsageFlags::TRANSFER_DST,
+        }
+    }
+
+    #[test]
+    fn negotiates_from_the_capabilities() {
+        let info = swapchain_create_info(
+            vk::SurfaceKHR::null(),
+            &capabilities(),
+            &[vk::PresentModeKHR::FIFO, vk::PresentModeKHR::MAILBOX],
+            &descriptor(),
+        )
+        .unwrap();
+
+        assert_eq!(info.present_mode, vk::PresentModeKHR::MAILBOX);
+        // The surface dictates the extent.
+        assert_eq!(
+            (info.image_extent.width, info.image_extent.height),
+            (800, 600)
+        );
+        assert_eq!(info.min_image_count, 3);
+        assert_eq!(info.pre_transform, vk::SurfaceTransformFlagsKHR::IDENTITY);
+        assert_eq!(info.composite_alpha, vk::CompositeAlphaFlagsKHR::INHERIT);
+        assert_eq!(info.image_format, BGRA8_SRGB.format);
+        assert_eq!(info.image_color_space, BGRA8_SRGB.color_space);
+    }
+
+    #[test]
+    fn clamps_the_window_size_and_image_count() {
+        let capabilities = vk::SurfaceCapabilitiesKHR {
+            min_image_count: 3,
+            max_image_count: 3,
+            current_extent: vk::Extent2D {
+                width: u32::MAX,
+                height: u32::MAX,
+            },
+            max_image_extent: vk::Extent2D {
+                width: 1024,
+                height: 1024,
+            },
+            ..capabilities()
+        };
+
+        let info = swapchain_create_info(
+            vk::SurfaceKHR::null(),
+            &capabilities,
+            &[vk::PresentModeKHR::FIFO],
+            &descriptor(),
+        )
+        .unwrap();
+
+        assert_eq!(info.present_mode, vk::PresentModeKHR::FIFO);
+        assert_eq!(
+            (info.image_extent.width, info.image_extent.height),
+            (1024, 720)
+        );
+        assert_eq!(info.min_image_count, 3);
+    }
+
+    #[test]
+    fn rejects_zero_extents() {
+        let minimized = vk::SurfaceCapabilitiesKHR {
+            current_extent: vk::Extent2D {
+                width: 0,
+                height: 0,
+            },
+            ..capabilities()
+        };
+        let error = swapchain_create_info(
+            vk::SurfaceKHR::null(),
+            &minimized,
+            &[vk::PresentModeKHR::FIFO],
+            &descriptor(),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("0x0"), "{}", error);
+
+        let unsized_surface = vk::SurfaceCapabilitiesKHR {
+            current_extent: vk::Extent2D {
+                width: u32::MAX,
+                height: u32::MAX,
+            },
+            min_image_extent: vk::Extent2D {
+                width: 0,
+                height: 0,
+            },
+            ..capabilities()
+        };
+        let descriptor = SwapchainDescriptor {
+            height: 0,
+            ..descriptor()
+        };
+        assert!(swapchain_create_info(
+            vk::SurfaceKHR::null(),
+            &unsized_surface,
+            &[vk::PresentModeKHR::FIFO],
+            &descriptor,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_usage() {
+        let descriptor = SwapchainDescriptor {
+            usage: vk::ImageUsageFlags::STORAGE,
+            ..descriptor()
+        };
+
+        assert!(swapchain_create_info(
+            vk::SurfaceKHR::null(),
+            &capabilities(),
+            &[vk::PresentModeKHR::FIFO],
+            &descriptor,
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,303 @@
+use crate::VertexAttribute;
+use ash::vk;
+
+/// A vertex type with a fixed memory layout, usually implemented with `#[derive(Vertex)]` from
+/// the `derive` feature.
+pub trait Vertex: Sized {
+    /// Each attribute along with its byte offset, in location order.
+    const ATTRIBUTES: &'static [(VertexAttribute, u32)];
+    /// Whether the binding advances per instance rather than per vertex.
+    const PER_INSTANCE: bool = false;
+
+    fn binding_description(binding: u32) -> vk::VertexInputBindingDescription {
+        *vk::VertexInputBindingDescription::builder()
+            .binding(binding)
+            .stride(std::mem::size_of::<Self>() as u32)
+            .input_rate(if Self::PER_INSTANCE {
+                vk::VertexInputRate::INSTANCE
+            } else {
+                vk::VertexInputRate::VERTEX
+            })
+    }
+
+    /// The attribute descriptions, starting at `first_location`.
+    fn attribute_descriptions(
+        binding: u32,
+        first_location: u32,
+    ) -> Vec<vk::VertexInputAttributeDescription> {
+        let mut descriptions = Vec::with_capacity(Self::ATTRIBUTES.len());
+        let mut location = first_location;
+
+        for &(attribute, offset) in Self::ATTRIBUTES {
+            attribute.push_descriptions(binding, offset, &mut location, &mut descriptions);
+        }
+
+        descriptions
+    }
+
+    /// The number of shader locations that the attributes take up.
+    fn location_count() -> u32 {
+        Self::ATTRIBUTES
+            .iter()
+            .map(|(attribute, _)| attribute.location_count())
+            .sum()
+    }
+}
+
+/// The default [`VertexAttribute`] for a field type in `#[derive(Vertex)]`.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` doesn't have a default vertex attribute format",
+    note = "use `#[vertex(format = ...)]` to pick a `VertexAttribute` for the field"
+)]
+pub trait VertexFormat {
+    const ATTRIBUTE: VertexAttribute;
+}
+
+macro_rules! vertex_formats {
+    ($($ty:ty => $attribute:ident),* $(,)?) => {
+        $(
+            impl VertexFormat for $ty {
+                const ATTRIBUTE: VertexAttribute = VertexAttribute::$attribute;
+            }
+        )*
+    };
+}
+
+vertex_formats! {
+    f32 => Float,
+    [f32; 2] => Vec2,
+    [f32; 3] => Vec3,
+    [f32; 4] => Vec4,
+    i32 => Int,
+    [i32; 2] => IVec2,
+    [i32; 3] => IVec3,
+    [i32; 4] => IVec4,
+    u32 => Uint,
+    [u32; 2] => UVec2,
+    [u32; 3] => UVec3,
+    [u32; 4] => UVec4,
+    [[f32; 4]; 4] => Mat4,
+}
+
+/// The attributes of one vertex buffer binding, for [`create_vertex_input_layout`].
+pub struct VertexBindingLayout<'a> {
+    pub attributes: &'a [VertexAttribute],
+    pub input_rate: vk::VertexInputRate,
+    /// Extra bytes after the last attribute, for vertices that contain data the shaders don't
+    /// read.
+    pub padding: u32,
+    /// The location of the first attribute. If `None`, the attributes follow on from the
+    /// previous binding, starting at 0.
+    pub first_location: Option<u32>,
+}
+
+impl<'a> VertexBindingLayout<'a> {
+    /// A tightly packed per-vertex binding.
+    pub fn per_vertex(attributes: &'a [VertexAttribute]) -> Self {
+        Self {
+            attributes,
+            input_rate: vk::VertexInputRate::VERTEX,
+            padding: 0,
+            first_location: None,
+        }
+    }
+
+    /// A tightly packed per-instance binding.
+    pub fn per_instance(attributes: &'a [VertexAttribute]) -> Self {
+        Self {
+            input_rate: vk::VertexInputRate::INSTANCE,
+            ..Self::per_vertex(attributes)
+        }
+    }
+
+    pub fn stride(&self) -> u32 {
+        self.attributes
+            .iter()
+            .map(|attribute| attribute.size())
+            .sum::<u32>()
+            + self.padding
+    }
+}
+
+/// Binding and attribute descriptions for a [`GraphicsPipelineDescriptor`](crate::GraphicsPipelineDescriptor).
+#[derive(Clone, Debug, Default)]
+pub struct VertexInputLayout {
+    pub bindings: Vec<vk::VertexInputBindingDescription>,
+    pub attributes: Vec<vk::VertexInputAttributeDescription>,
+}
+
+/// Like [`create_vertex_attribute_descriptions`](crate::create_vertex_attribute_descriptions),
+/// but also creates the binding descriptions with their strides and input rates. Bindings are
+/// numbered in order.
+pub fn create_vertex_input_layout(bindings: &[VertexBindingLayout]) -> VertexInputLayout {
+    let mut layout = VertexInputLayout::default();
+    let mut location = 0;
+
+    for (binding, binding_layout) in bindings.iter().enumerate() {
+        let binding = binding as u32;
+
+        layout.bindings.push(
+            *vk::VertexInputBindingDescription::builder()
+                .binding(binding)
+                .stride(binding_layout.stride())
+                .input_rate(binding_layout.input_rate),
+        );
+
+        if let Some(first_location) = binding_layout.first_location {
+            location = first_location;
+        }
+
+        let mut offset = 0;
+
+        for attribute in binding_layout.attributes {
+            attribute.push_descriptions(binding, offset, &mut location, &mut layout.attributes);
+            offset += attribute.size();
+        }
+    }
+
+    layout
+}
+
+/// Structs that `#[derive(Vertex)]` has to reject.
+///
+/// Padding before a field:
+///
+/// ```compile_fail,E0080
+/// #[derive(ash_opinionated_abstractions::Vertex)]
+/// #[repr(C)]
+/// struct Padded {
+///     position: f32,
+///     #[vertex(format = Unorm16x4)]
+///     color: u64,
+/// }
+/// ```
+///
+/// Padding after the last field:
+///
+/// ```compile_fail,E0080
+/// #[derive(ash_opinionated_abstractions::Vertex)]
+/// #[repr(C, align(16))]
+/// struct Aligned {
+///     position: [f32; 3],
+/// }
+/// ```
+///
+/// A format that doesn't match the size of the field:
+///
+/// ```compile_fail,E0080
+/// #[derive(ash_opinionated_abstractions::Vertex)]
+/// #[repr(C)]
+/// struct WrongFormat {
+///     #[vertex(format = Vec4)]
+///     color: [u8; 4],
+/// }
+/// ```
+///
+/// A field type without a default format:
+///
+/// ```compile_fail,E0277
+/// #[derive(ash_opinionated_abstractions::Vertex)]
+/// #[repr(C)]
+/// struct NoFormat {
+///     color: [u8; 4],
+/// }
+/// ```
+///
+/// A missing `#[repr(C)]`:
+///
+/// ```compile_fail
+/// #[derive(ash_opinionated_abstractions::Vertex)]
+/// struct NotReprC {
+///     position: [f32; 3],
+/// }
+/// ```
+///
+/// An unknown attribute:
+///
+/// ```compile_fail
+/// #[derive(ash_opinionated_abstractions::Vertex)]
+/// #[vertex(instanced)]
+/// #[repr(C)]
+/// struct Typo {
+///     position: [f32; 3],
+/// }
+/// ```
+#[cfg(all(doctest, feature = "derive"))]
+struct DeriveVertexCompileFail;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strides_include_padding() {
+        let layout = VertexBindingLayout {
+            padding: 4,
+            ..VertexBindingLayout::per_vertex(&[VertexAttribute::Vec3, VertexAttribute::Unorm8x4])
+        };
+
+        assert_eq!(layout.stride(), 20);
+        assert_eq!(
+            VertexBindingLayout::per_instance(&[VertexAttribute::Mat4]).stride(),
+            64
+        );
+    }
+
+    #[test]
+    fn creates_bindings_and_locations() {
+        let layout = create_vertex_input_layout(&[
+            VertexBindingLayout {
+                padding: 4,
+                ..VertexBindingLayout::per_vertex(&[
+                    VertexAttribute::Vec3,
+                    VertexAttribute::Unorm8x4,
+                ])
+            },
+            VertexBindingLayout::per_instance(&[VertexAttribute::Mat4, VertexAttribute::Vec4]),
+            VertexBindingLayout {
+                first_location: Some(10),
+                ..VertexBindingLayout::per_vertex(&[VertexAttribute::Half2])
+            },
+            VertexBindingLayout::per_vertex(&[VertexAttribute::Float]),
+        ]);
+
+        let bindings: Vec<_> = layout
+            .bindings
+            .iter()
+            .map(|binding| (binding.binding, binding.stride, binding.input_rate))
+            .collect();
+
+        assert_eq!(
+            bindings,
+            [
+                (0, 20, vk::VertexInputRate::VERTEX),
+                (1, 80, vk::VertexInputRate::INSTANCE),
+                (2, 4, vk::VertexInputRate::VERTEX),
+                (3, 4, vk::VertexInputRate::VERTEX),
+            ]
+        );
+
+        let attributes: Vec<_> = layout
+            .attributes
+            .iter()
+            .map(|attribute| (attribute.binding, attribute.location, attribute.offset))
+            .collect();
+
+        // The matrix carries on from the first binding and takes up four locations, and the last
+        // binding carries on from the one that reset the location.
+        assert_eq!(
+            attributes,
+            [
+                (0, 0, 0),
+                (0, 1, 12),
+                (1, 2, 0),
+                (1, 3, 16),
+                (1, 4, 32),
+                (1, 5, 48),
+                (1, 6, 64),
+                (2, 10, 0),
+                (3, 11, 0),
+            ]
+        );
+    }
+}
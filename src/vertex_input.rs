@@ -0,0 +1,344 @@
+use crate::{NumericType, ShaderInput, ShaderReflection};
+use ash::vk;
+
+/// The component type, bit width and component count of a vertex attribute format.
+fn format_components(format: vk::Format) -> Option<(NumericType, u32, u32)> {
+    use vk::Format as F;
+
+    let float = |count| Some((NumericType::Float, 32, count));
+    let sint = |count| Some((NumericType::Sint, 32, count));
+    let uint = |count| Some((NumericType::Uint, 32, count));
+
+    // Normalized, scaled and half-float formats are all read as 32-bit floats in the shader.
+    match format {
+        F::R8_UNORM | F::R8_SNORM | F::R8_USCALED | F::R8_SSCALED | F::R8_SRGB => float(1),
+        F::R16_UNORM | F::R16_SNORM | F::R16_USCALED | F::R16_SSCALED | F::R16_SFLOAT => float(1),
+        F::R32_SFLOAT => float(1),
+        F::R8G8_UNORM | F::R8G8_SNORM | F::R8G8_USCALED | F::R8G8_SSCALED | F::R8G8_SRGB => {
+            float(2)
+        }
+        F::R16G16_UNORM
+        | F::R16G16_SNORM
+        | F::R16G16_USCALED
+        | F::R16G16_SSCALED
+        | F::R16G16_SFLOAT => float(2),
+        F::R32G32_SFLOAT => float(2),
+        F::R8G8B8_UNORM
+        | F::R8G8B8_SNORM
+        | F::R8G8B8_USCALED
+        | F::R8G8B8_SSCALED
+        | F::R8G8B8_SRGB
+        | F::B8G8R8_UNORM
+        | F::B8G8R8_SNORM
+        | F::B8G8R8_SRGB => float(3),
+        F::R16G16B16_UNORM
+        | F::R16G16B16_SNORM
+        | F::R16G16B16_USCALED
+        | F::R16G16B16_SSCALED
+        | F::R16G16B16_SFLOAT => float(3),
+        F::R32G32B32_SFLOAT | F::B10G11R11_UFLOAT_PACK32 => float(3),
+        F::R8G8B8A8_UNORM
+        | F::R8G8B8A8_SNORM
+        | F::R8G8B8A8_USCALED
+        | F::R8G8B8A8_SSCALED
+        | F::R8G8B8A8_SRGB
+        | F::B8G8R8A8_UNORM
+        | F::B8G8R8A8_SNORM
+        | F::B8G8R8A8_SRGB
+        | F::A8B8G8R8_UNORM_PACK32
+        | F::A8B8G8R8_SNORM_PACK32
+        | F::A8B8G8R8_USCALED_PACK32
+        | F::A8B8G8R8_SSCALED_PACK32
+        | F::A8B8G8R8_SRGB_PACK32 => float(4),
+        F::A2B10G10R10_UNORM_PACK32
+        | F::A2B10G10R10_SNORM_PACK32
+        | F::A2B10G10R10_USCALED_PACK32
+        | F::A2B10G10R10_SSCALED_PACK32
+        | F::A2R10G10B10_UNORM_PACK32
+        | F::A2R10G10B10_SNORM_PACK32 => float(4),
+        F::R16G16B16A16_UNORM
+        | F::R16G16B16A16_SNORM
+        | F::R16G16B16A16_USCALED
+        | F::R16G16B16A16_SSCALED
+        | F::R16G16B16A16_SFLOAT => float(4),
+        F::R32G32B32A32_SFLOAT => float(4),
+        F::R8_SINT | F::R16_SINT | F::R32_SINT => sint(1),
+        F::R8G8_SINT | F::R16G16_SINT | F::R32G32_SINT => sint(2),
+        F::R8G8B8_SINT | F::B8G8R8_SINT | F::R16G16B16_SINT | F::R32G32B32_SINT => sint(3),
+        F::R8G8B8A8_SINT
+        | F::B8G8R8A8_SINT
+        | F::A8B8G8R8_SINT_PACK32
+        | F::A2B10G10R10_SINT_PACK32
+        | F::R16G16B16A16_SINT
+        | F::R32G32B32A32_SINT => sint(4),
+        F::R8_UINT | F::R16_UINT | F::R32_UINT => uint(1),
+        F::R8G8_UINT | F::R16G16_UINT | F::R32G32_UINT => uint(2),
+        F::R8G8B8_UINT | F::B8G8R8_UINT | F::R16G16B16_UINT | F::R32G32B32_UINT => uint(3),
+        F::R8G8B8A8_UINT
+        | F::B8G8R8A8_UINT
+        | F::A8B8G8R8_UINT_PACK32
+        | F::A2B10G10R10_UINT_PACK32
+        | F::R16G16B16A16_UINT
+        | F::R32G32B32A32_UINT => uint(4),
+        F::R64_SFLOAT => Some((NumericType::Float, 64, 1)),
+        F::R64G64_SFLOAT => Some((NumericType::Float, 64, 2)),
+        F::R64G64B64_SFLOAT => Some((NumericType::Float, 64, 3)),
+        F::R64G64B64A64_SFLOAT => Some((NumericType::Float, 64, 4)),
+        F::R64_SINT => Some((NumericType::Sint, 64, 1)),
+        F::R64_UINT => Some((NumericType::Uint, 64, 1)),
+        _ => None,
+    }
+}
+
+/// Check that vertex attribute descriptions, such as those from
+/// [`create_vertex_attribute_descriptions`](crate::create_vertex_attribute_descriptions), match
+/// the inputs that a vertex shader declares.
+///
+/// Every location used by the shader needs an attribute with the same component type and count.
+/// All of the mismatches are listed in the returned error.
+pub fn validate_vertex_input(
+    vertex_shader: &ShaderReflection,
+    attributes: &[vk::VertexInputAttributeDescription],
+) -> anyhow::Result<()> {
+    if vertex_shader.entry_point.stage != vk::ShaderStageFlags::VERTEX {
+        return Err(anyhow::anyhow!(
+            "Entry point {:?} is a {:?} shader, not a vertex shader",
+            vertex_shader.entry_point.name,
+            vertex_shader.entry_point.stage
+        ));
+    }
+
+    let mut mismatches = Vec::new();
+
+    for input in &vertex_shader.inputs {
+        // 64-bit vectors with more than two components are fed by a single attribute that
+        // spans two locations.
+        let step = if input.bit_width == 64 && input.component_count > 2 {
+            2
+        } else {
+            1
+        };
+
+        for location in (input.location..input.location + input.location_count).step_by(step) {
+            let attribute = attributes
+                .iter()
+                .find(|attribute| attribute.location == location);
+
+            if let Some(mismatch) = check_attribute(input, location, attribute) {
+                mismatches.push(mismatch);
+            }
+        }
+    }
+
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+
+    Err(anyhow::anyhow!(
+        "The vertex input doesn't match the inputs of vertex shader {:?}:\n  {}",
+        vertex_shader.entry_point.name,
+        mismatches.join("\n  ")
+    ))
+}
+
+fn check_attribute(
+    input: &ShaderInput,
+    location: u32,
+    attribute: Option<&vk::VertexInputAttributeDescription>,
+) -> Option<String> {
+    let input_name = match &input.name {
+        Some(name) => format!("location {} ({:?})", location, name),
+        None => format!("location {}", location),
+    };
+
+    let attribute = match attribute {
+        Some(attribute) => attribute,
+        None => {
+            return Some(format!(
+                "{}: the shader expects a {} but there is no attribute at this location",
+                input_name,
+                input.type_name()
+            ))
+        }
+    };
+
+    let (numeric_type, bit_width, component_count) = match format_components(attribute.format) {
+        Some(components) => components,
+        None => {
+            return Some(format!(
+                "{}: {:?} is not a supported vertex attribute format",
+                input_name, attribute.format
+            ))
+        }
+    };
+
+    if numeric_type != input.numeric_type
+        || bit_width != input.bit_width
+        || component_count != input.component_count
+    {
+        let attribute_type = ShaderInput {
+            numeric_type,
+            bit_width,
+            component_count,
+            ..input.clone()
+        };
+
+        return Some(format!(
+            "{}: the shader expects a {} but the attribute is {:?}, which is read as a {}",
+            input_name,
+            input.type_name(),
+            attribute.format,
+            attribute_type.type_name()
+        ));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflection::tests::vertex_shader;
+
+    fn attributes(formats: &[(u32, vk::Format)]) -> Vec<vk::VertexInputAttributeDescription> {
+        formats
+            .iter()
+            .map(|&(location, format)| vk::VertexInputAttributeDescription {
+                location,
+                binding: 0,
+                format,
+                offset: 0,
+            })
+            .collect()
+    }
+
+    fn error(shader: &ShaderReflection, formats: &[(u32, vk::Format)]) -> String {
+        validate_vertex_input(shader, &attributes(formats))
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn accepts_matching_attributes() {
+        let shader = vertex_shader(&[(0, "vec3"), (1, "vec2"), (2, "uvec4")]);
+
+        validate_vertex_input(
+            &shader,
+            &attributes(&[
+                (0, vk::Format::R32G32B32_SFLOAT),
+                // Normalized formats are read as floats.
+                (1, vk::Format::R16G16_UNORM),
+                (2, vk::Format::R8G8B8A8_UINT),
+                // Attributes that the shader doesn't use are fine.
+                (3, vk::Format::R32_SFLOAT),
+            ]),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn rejects_component_count_mismatches() {
+        let shader = vertex_shader(&[(0, "vec3")]);
+
+        assert!(
+            error(&shader, &[(0, vk::Format::R32G32B32A32_SFLOAT)]).ends_with(
+                "location 0: the shader expects a vec3 but the attribute is R32G32B32A32_SFLOAT, \
+             which is read as a vec4"
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_missing_locations() {
+        let shader = vertex_shader(&[(0, "vec3"), (1, "vec2")]);
+
+        assert!(
+            error(&shader, &[(0, vk::Format::R32G32B32_SFLOAT)]).ends_with(
+                "location 1: the shader expects a vec2 but there is no attribute at this location"
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_numeric_type_mismatches() {
+        let shader = vertex_shader(&[(0, "ivec4")]);
+
+        assert!(
+            error(&shader, &[(0, vk::Format::R32G32B32A32_SFLOAT)]).ends_with(
+                "location 0: the shader expects a ivec4 but the attribute is R32G32B32A32_SFLOAT, \
+             which is read as a vec4"
+            )
+        );
+        validate_vertex_input(&shader, &attributes(&[(0, vk::Format::R8G8B8A8_SINT)])).unwrap();
+    }
+
+    #[test]
+    fn matrices_need_an_attribute_per_column() {
+        let shader = vertex_shader(&[(1, "mat4")]);
+        let column = vk::Format::R32G32B32A32_SFLOAT;
+
+        validate_vertex_input(
+            &shader,
+            &attributes(&[(1, column), (2, column), (3, column), (4, column)]),
+        )
+        .unwrap();
+
+        assert!(
+            error(&shader, &[(1, column), (2, column), (3, column)]).ends_with(
+                "location 4: the shader expects a vec4 but there is no attribute at this location"
+            )
+        );
+    }
+
+    #[test]
+    fn wide_64_bit_vectors_take_two_locations() {
+        let shader = vertex_shader(&[(0, "dvec4"), (2, "dvec3"), (4, "dvec2")]);
+
+        // One attribute covers both locations of a dvec3 or dvec4.
+        validate_vertex_input(
+            &shader,
+            &attributes(&[
+                (0, vk::Format::R64G64B64A64_SFLOAT),
+                (2, vk::Format::R64G64B64_SFLOAT),
+                (4, vk::Format::R64G64_SFLOAT),
+            ]),
+        )
+        .unwrap();
+
+        assert!(error(
+            &shader,
+            &[
+                (0, vk::Format::R32G32B32A32_SFLOAT),
+                (2, vk::Format::R64G64B64_SFLOAT),
+                (4, vk::Format::R64G64_SFLOAT),
+            ]
+        )
+        .ends_with(
+            "location 0: the shader expects a dvec4 but the attribute is R32G32B32A32_SFLOAT, \
+             which is read as a vec4"
+        ));
+    }
+
+    #[test]
+    fn lists_every_mismatch() {
+        let shader = vertex_shader(&[(0, "vec3"), (1, "uint"), (2, "vec2"), (3, "float")]);
+
+        assert_eq!(
+            error(
+                &shader,
+                &[
+                    (0, vk::Format::R32G32_SFLOAT),
+                    (1, vk::Format::R32_SINT),
+                    (3, vk::Format::D32_SFLOAT),
+                ]
+            ),
+            "The vertex input doesn't match the inputs of vertex shader \"main\":\n  \
+             location 0: the shader expects a vec3 but the attribute is R32G32_SFLOAT, which is \
+             read as a vec2\n  \
+             location 1: the shader expects a uint but the attribute is R32_SINT, which is read \
+             as a int\n  \
+             location 2: the shader expects a vec2 but there is no attribute at this location\n  \
+             location 3: D32_SFLOAT is not a supported vertex attribute format"
+        );
+    }
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "derive")]
+
+use ash::vk;
+use ash_opinionated_abstractions::{Vertex, VertexAttribute};
+
+#[derive(Vertex)]
+#[repr(C)]
+struct ColoredVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    #[vertex(format = Unorm8x4)]
+    color: [u8; 4],
+    uv: [f32; 2],
+}
+
+#[derive(Vertex)]
+#[vertex(instance)]
+#[repr(C)]
+struct Instance([[f32; 4]; 4], u32);
+
+#[test]
+fn packed_struct_offsets() {
+    assert_eq!(
+        ColoredVertex::ATTRIBUTES,
+        &[
+            (VertexAttribute::Vec3, 0),
+            (VertexAttribute::Vec3, 12),
+            (VertexAttribute::Unorm8x4, 24),
+            (VertexAttribute::Vec2, 28),
+        ]
+    );
+    assert_eq!(ColoredVertex::location_count(), 4);
+
+    let binding = ColoredVertex::binding_description(1);
+    assert_eq!(binding.binding, 1);
+    assert_eq!(binding.stride, 36);
+    assert_eq!(binding.input_rate, vk::VertexInputRate::VERTEX);
+
+    let attributes = ColoredVertex::attribute_descriptions(1, 0);
+    let formats: Vec<_> = attributes
+        .iter()
+        .map(|attribute| (attribute.location, attribute.format, attribute.offset))
+        .collect();
+    assert_eq!(
+        formats,
+        [
+            (0, vk::Format::R32G32B32_SFLOAT, 0),
+            (1, vk::Format::R32G32B32_SFLOAT, 12),
+            (2, vk::Format::R8G8B8A8_UNORM, 24),
+            (3, vk::Format::R32G32_SFLOAT, 28),
+        ]
+    );
+    assert!(attributes.iter().all(|attribute| attribute.binding == 1));
+}
+
+#[test]
+fn per_instance_matrices() {
+    assert_eq!(Instance::location_count(), 5);
+
+    let binding = Instance::binding_description(0);
+    assert_eq!(binding.stride, 68);
+    assert_eq!(binding.input_rate, vk::VertexInputRate::INSTANCE);
+
+    // Each column of the matrix takes up its own location.
+    let attributes = Instance::attribute_descriptions(0, 4);
+    let formats: Vec<_> = attributes
+        .iter()
+        .map(|attribute| (attribute.location, attribute.format, attribute.offset))
+        .collect();
+    assert_eq!(
+        formats,
+        [
+            (4, vk::Format::R32G32B32A32_SFLOAT, 0),
+            (5, vk::Format::R32G32B32A32_SFLOAT, 16),
+            (6, vk::Format::R32G32B32A32_SFLOAT, 32),
+            (7, vk::Format::R32G32B32A32_SFLOAT, 48),
+            (8, vk::Format::R32_UINT, 64),
+        ]
+    );
+}